@@ -1,4 +1,12 @@
-use crate::token::Token;
+use crate::{stmt::Stmt, token::Token};
+
+#[derive(PartialEq, Clone, Debug)]
+pub enum Literal {
+    Number(f64),
+    String(String),
+    Boolean(bool),
+    Nil,
+}
 
 #[derive(PartialEq, Clone, Debug)]
 pub enum Expr {
@@ -16,12 +24,19 @@ pub enum Expr {
         paren: Token,
         args: Vec<Expr>,
     },
+    Interpolation {
+        parts: Vec<Expr>,
+    },
+    TypeTest {
+        value: Box<Expr>,
+        operator: Token,
+        type_name: Token,
+    },
     Grouping {
         expr: Box<Expr>,
     },
-    // TODO: Split to different literal types
     Literal {
-        value: Token,
+        value: Literal,
     },
     Variable {
         name: Token,
@@ -40,19 +55,33 @@ pub enum Expr {
         operator: Token,
         right: Box<Expr>,
     },
+    Coalesce {
+        left: Box<Expr>,
+        operator: Token,
+        right: Box<Expr>,
+    },
+    // A block in expression position, evaluating to its trailing expression (if any).
+    Block {
+        stmts: Vec<Stmt>,
+        value: Option<Box<Expr>>,
+    },
 }
 
 pub trait Visitor {
     type Out;
-    fn visit_literal(&mut self, value: &Token) -> Self::Out;
+    fn visit_literal(&mut self, value: &Literal) -> Self::Out;
     fn visit_unary(&mut self, operator: &Token, right: &Expr) -> Self::Out;
     fn visit_call(&mut self, callee: &Expr, paren: &Token, args: &[Expr]) -> Self::Out;
+    fn visit_interpolation(&mut self, parts: &[Expr]) -> Self::Out;
+    fn visit_type_test(&mut self, value: &Expr, type_name: &Token) -> Self::Out;
     fn visit_grouping(&mut self, expr: &Expr) -> Self::Out;
     fn visit_binary(&mut self, left: &Expr, operator: &Token, right: &Expr) -> Self::Out;
     fn visit_variable(&mut self, name: &Token) -> Self::Out;
     fn visit_assignment(&mut self, name: &Token, value: &Expr) -> Self::Out;
     fn visit_logic_or(&mut self, left: &Expr, right: &Expr) -> Self::Out;
     fn visit_logic_and(&mut self, left: &Expr, right: &Expr) -> Self::Out;
+    fn visit_coalesce(&mut self, left: &Expr, right: &Expr) -> Self::Out;
+    fn visit_block_expression(&mut self, stmts: &[Stmt], value: Option<&Expr>) -> Self::Out;
 }
 
 impl Expr {
@@ -76,6 +105,12 @@ impl Expr {
                 ref paren,
                 ref args,
             } => visitor.visit_call(callee, paren, args),
+            Expr::Interpolation { ref parts } => visitor.visit_interpolation(parts),
+            Expr::TypeTest {
+                ref value,
+                ref type_name,
+                ..
+            } => visitor.visit_type_test(value, type_name),
             Expr::Grouping { ref expr } => visitor.visit_grouping(expr),
             Expr::Variable { ref name } => visitor.visit_variable(name),
             Expr::Assignment {
@@ -92,6 +127,15 @@ impl Expr {
                 ref right,
                 ..
             } => visitor.visit_logic_and(left, right),
+            Expr::Coalesce {
+                ref left,
+                ref right,
+                ..
+            } => visitor.visit_coalesce(left, right),
+            Expr::Block {
+                ref stmts,
+                ref value,
+            } => visitor.visit_block_expression(stmts, value.as_deref()),
         }
     }
 }
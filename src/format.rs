@@ -0,0 +1,462 @@
+use crate::{
+    expr::{self, Expr, Literal},
+    scanner::escape,
+    stmt::{self, FunctionDeclaration, Stmt, SwitchCase},
+    token::Token,
+};
+
+const INDENT: &str = "    ";
+
+// Renders syntax trees back into Lox source. Comments and layout are lost.
+pub struct Formatter {
+    indent: usize,
+}
+
+impl Formatter {
+    pub fn format(stmts: &[Stmt]) -> String {
+        let mut formatter = Formatter { indent: 0 };
+        stmts
+            .iter()
+            .map(|stmt| stmt.accept(&mut formatter) + "\n")
+            .collect()
+    }
+
+    fn pad(&self) -> String {
+        INDENT.repeat(self.indent)
+    }
+
+    fn block(&mut self, stmts: &[Stmt]) -> String {
+        if stmts.is_empty() {
+            return "{}".to_string();
+        }
+        self.indent += 1;
+        let mut out = String::from("{\n");
+        for stmt in stmts {
+            out += &format!("{}{}\n", self.pad(), stmt.accept(self));
+        }
+        self.indent -= 1;
+        out + &self.pad() + "}"
+    }
+
+    // A block stays on the header's line, anything else goes on its own.
+    fn body(&mut self, stmt: &Stmt) -> String {
+        match stmt {
+            Stmt::Block { stmts } => format!(" {}", self.block(stmts)),
+            stmt => {
+                self.indent += 1;
+                let out = format!("\n{}{}", self.pad(), stmt.accept(self));
+                self.indent -= 1;
+                out
+            }
+        }
+    }
+
+    fn after_body(&self, body: &Stmt) -> String {
+        match body {
+            Stmt::Block { .. } => " ".to_string(),
+            _ => format!("\n{}", self.pad()),
+        }
+    }
+
+    fn list(&mut self, exprs: &[Expr]) -> String {
+        let parts: Vec<String> = exprs.iter().map(|expr| expr.accept(self)).collect();
+        parts.join(", ")
+    }
+}
+
+impl expr::Visitor for Formatter {
+    type Out = String;
+
+    fn visit_literal(&mut self, value: &Literal) -> Self::Out {
+        match value {
+            Literal::Number(num) => num.to_string(),
+            Literal::String(string) => format!("\"{}\"", escape(string)),
+            Literal::Boolean(boolean) => boolean.to_string(),
+            Literal::Nil => "nil".to_string(),
+        }
+    }
+
+    fn visit_unary(&mut self, operator: &Token, right: &Expr) -> Self::Out {
+        format!("{}{}", operator.text, right.accept(self))
+    }
+
+    fn visit_call(&mut self, callee: &Expr, _paren: &Token, args: &[Expr]) -> Self::Out {
+        format!("{}({})", callee.accept(self), self.list(args))
+    }
+
+    fn visit_interpolation(&mut self, parts: &[Expr]) -> Self::Out {
+        let mut out = String::from("\"");
+        for (i, part) in parts.iter().enumerate() {
+            match part {
+                // Text and expressions alternate, starting and ending with text.
+                Expr::Literal {
+                    value: Literal::String(text),
+                } if i % 2 == 0 => out += &escape(text),
+                part => out += &format!("${{{}}}", part.accept(self)),
+            }
+        }
+        out + "\""
+    }
+
+    fn visit_type_test(&mut self, value: &Expr, type_name: &Token) -> Self::Out {
+        format!("{} is {}", value.accept(self), type_name.text)
+    }
+
+    fn visit_grouping(&mut self, expr: &Expr) -> Self::Out {
+        format!("({})", expr.accept(self))
+    }
+
+    fn visit_binary(&mut self, left: &Expr, operator: &Token, right: &Expr) -> Self::Out {
+        format!(
+            "{} {} {}",
+            left.accept(self),
+            operator.text,
+            right.accept(self)
+        )
+    }
+
+    fn visit_variable(&mut self, name: &Token) -> Self::Out {
+        name.text.clone()
+    }
+
+    fn visit_assignment(&mut self, name: &Token, value: &Expr) -> Self::Out {
+        format!("{} = {}", name.text, value.accept(self))
+    }
+
+    fn visit_logic_or(&mut self, left: &Expr, right: &Expr) -> Self::Out {
+        self.logical(left, "or", right)
+    }
+
+    fn visit_logic_and(&mut self, left: &Expr, right: &Expr) -> Self::Out {
+        self.logical(left, "and", right)
+    }
+
+    fn visit_coalesce(&mut self, left: &Expr, right: &Expr) -> Self::Out {
+        format!("{} ?? {}", left.accept(self), right.accept(self))
+    }
+
+    fn visit_block_expression(&mut self, stmts: &[Stmt], value: Option<&Expr>) -> Self::Out {
+        let Some(value) = value else {
+            return self.block(stmts);
+        };
+        self.indent += 1;
+        let mut out = String::from("{\n");
+        for stmt in stmts {
+            out += &format!("{}{}\n", self.pad(), stmt.accept(self));
+        }
+        out += &format!("{}{}\n", self.pad(), value.accept(self));
+        self.indent -= 1;
+        out + &self.pad() + "}"
+    }
+}
+
+impl Formatter {
+    // `x or (x = y)` without a grouping came from `x or= y`.
+    fn logical(&mut self, left: &Expr, operator: &str, right: &Expr) -> String {
+        if let (
+            Expr::Variable { name },
+            Expr::Assignment {
+                name: target,
+                value,
+            },
+        ) = (left, right)
+        {
+            if name.text == target.text {
+                return format!("{} {}= {}", name.text, operator, value.accept(self));
+            }
+        }
+        format!("{} {} {}", left.accept(self), operator, right.accept(self))
+    }
+}
+
+impl stmt::Visitor for Formatter {
+    type Out = String;
+
+    fn visit_expression(&mut self, expr: &Expr) -> Self::Out {
+        format!("{};", expr.accept(self))
+    }
+
+    fn visit_print(&mut self, exprs: &[Expr]) -> Self::Out {
+        format!("print {};", self.list(exprs))
+    }
+
+    fn visit_function_declaration(
+        &mut self,
+        function_declaration: &FunctionDeclaration,
+    ) -> Self::Out {
+        let params: Vec<String> = function_declaration
+            .params
+            .iter()
+            .map(|param| match &param.default {
+                Some(default) => format!("{} = {}", param.name.text, default.accept(self)),
+                None => param.name.text.clone(),
+            })
+            .collect();
+        format!(
+            "fun {}({}) {}",
+            function_declaration.name.text,
+            params.join(", "),
+            self.block(&function_declaration.body)
+        )
+    }
+
+    fn visit_return(&mut self, _keyword: &Token, value: Option<&Expr>) -> Self::Out {
+        match value {
+            Some(value) => format!("return {};", value.accept(self)),
+            None => "return;".to_string(),
+        }
+    }
+
+    fn visit_throw(&mut self, _keyword: &Token, value: &Expr) -> Self::Out {
+        format!("throw {};", value.accept(self))
+    }
+
+    fn visit_break(&mut self, _keyword: &Token, label: Option<&Token>) -> Self::Out {
+        format!("break{};", label_suffix(label))
+    }
+
+    fn visit_continue(&mut self, _keyword: &Token, label: Option<&Token>) -> Self::Out {
+        format!("continue{};", label_suffix(label))
+    }
+
+    fn visit_variable_declaration(
+        &mut self,
+        name: &Token,
+        initializer: Option<&Expr>,
+    ) -> Self::Out {
+        format!("var {};", self.declarator(name, initializer))
+    }
+
+    fn visit_const_declaration(&mut self, name: &Token, initializer: &Expr) -> Self::Out {
+        format!("const {} = {};", name.text, initializer.accept(self))
+    }
+
+    fn visit_try(&mut self, body: &[Stmt], error_name: &Token, handler: &[Stmt]) -> Self::Out {
+        format!(
+            "try {} catch ({}) {}",
+            self.block(body),
+            error_name.text,
+            self.block(handler)
+        )
+    }
+
+    fn visit_block(&mut self, stmts: &[Stmt]) -> Self::Out {
+        self.block(stmts)
+    }
+
+    // Only `var a, b;` produces these.
+    fn visit_sequence(&mut self, stmts: &[Stmt]) -> Self::Out {
+        let declarators: Option<Vec<String>> = stmts
+            .iter()
+            .map(|stmt| match stmt {
+                Stmt::VariableDeclaration { name, initializer } => {
+                    Some(self.declarator(name, initializer.as_deref()))
+                }
+                _ => None,
+            })
+            .collect();
+        match declarators {
+            Some(declarators) => format!("var {};", declarators.join(", ")),
+            None => {
+                let stmts: Vec<String> = stmts.iter().map(|stmt| stmt.accept(self)).collect();
+                stmts.join(&format!("\n{}", self.pad()))
+            }
+        }
+    }
+
+    fn visit_if(
+        &mut self,
+        condition: &Expr,
+        then_branch: &Stmt,
+        else_branch: Option<&Stmt>,
+    ) -> Self::Out {
+        let mut out = format!("if ({}){}", condition.accept(self), self.body(then_branch));
+        match else_branch {
+            Some(else_branch @ Stmt::If { .. }) => {
+                out += &format!(
+                    "{}else {}",
+                    self.after_body(then_branch),
+                    else_branch.accept(self)
+                );
+            }
+            Some(else_branch) => {
+                out += &format!(
+                    "{}else{}",
+                    self.after_body(then_branch),
+                    self.body(else_branch)
+                );
+            }
+            None => {}
+        }
+        out
+    }
+
+    fn visit_switch(
+        &mut self,
+        discriminant: &Expr,
+        cases: &[SwitchCase],
+        default: Option<&[Stmt]>,
+    ) -> Self::Out {
+        let mut out = format!("switch ({}) {{\n", discriminant.accept(self));
+        self.indent += 1;
+        for case in cases {
+            let label = format!("case {}:", case.value.accept(self));
+            out += &self.case(&label, &case.body);
+        }
+        if let Some(default) = default {
+            out += &self.case("default:", default);
+        }
+        self.indent -= 1;
+        out + &self.pad() + "}"
+    }
+
+    fn visit_while(&mut self, condition: &Expr, body: &Stmt, label: Option<&Token>) -> Self::Out {
+        format!(
+            "{}while ({}){}",
+            label_prefix(label),
+            condition.accept(self),
+            self.body(body)
+        )
+    }
+
+    fn visit_for(
+        &mut self,
+        initializer: Option<&Stmt>,
+        condition: Option<&Expr>,
+        increment: Option<&Expr>,
+        body: &Stmt,
+        label: Option<&Token>,
+    ) -> Self::Out {
+        let initializer = initializer.map_or(";".to_string(), |stmt| stmt.accept(self));
+        let condition = condition.map_or(String::new(), |expr| format!(" {}", expr.accept(self)));
+        let increment = increment.map_or(String::new(), |expr| format!(" {}", expr.accept(self)));
+        format!(
+            "{}for ({}{};{}){}",
+            label_prefix(label),
+            initializer,
+            condition,
+            increment,
+            self.body(body)
+        )
+    }
+
+    fn visit_do_while(
+        &mut self,
+        body: &Stmt,
+        condition: &Expr,
+        label: Option<&Token>,
+    ) -> Self::Out {
+        format!(
+            "{}do{}{}while ({});",
+            label_prefix(label),
+            self.body(body),
+            self.after_body(body),
+            condition.accept(self)
+        )
+    }
+
+    fn visit_repeat(
+        &mut self,
+        _keyword: &Token,
+        count: &Expr,
+        body: &Stmt,
+        label: Option<&Token>,
+    ) -> Self::Out {
+        format!(
+            "{}repeat ({}){}",
+            label_prefix(label),
+            count.accept(self),
+            self.body(body)
+        )
+    }
+}
+
+impl Formatter {
+    fn declarator(&mut self, name: &Token, initializer: Option<&Expr>) -> String {
+        match initializer {
+            Some(initializer) => format!("{} = {}", name.text, initializer.accept(self)),
+            None => name.text.clone(),
+        }
+    }
+
+    fn case(&mut self, label: &str, body: &[Stmt]) -> String {
+        let mut out = format!("{}{}\n", self.pad(), label);
+        self.indent += 1;
+        for stmt in body {
+            out += &format!("{}{}\n", self.pad(), stmt.accept(self));
+        }
+        self.indent -= 1;
+        out
+    }
+}
+
+fn label_prefix(label: Option<&Token>) -> String {
+    label.map_or(String::new(), |label| format!("{}: ", label.text))
+}
+
+fn label_suffix(label: Option<&Token>) -> String {
+    label.map_or(String::new(), |label| format!(" {}", label.text))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{parser::Parser, scanner::Scanner, Result};
+
+    fn parse(source: &str) -> Result<Vec<Stmt>> {
+        let mut scanner = Scanner::new(source.to_string());
+        Parser::new(scanner.scan_tokens()?).parse()
+    }
+
+    // Tokens record where they were in the source, which formatting moves.
+    fn without_positions(stmts: &[Stmt]) -> String {
+        let mut debug = format!("{stmts:?}");
+        for field in ["line: ", "start: ", "end: "] {
+            let mut rest = debug.as_str();
+            let mut out = String::new();
+            while let Some(i) = rest.find(field) {
+                out += &rest[..i + field.len()];
+                rest = rest[i + field.len()..].trim_start_matches(|c: char| c.is_ascii_digit());
+            }
+            debug = out + rest;
+        }
+        debug
+    }
+
+    const PROGRAM: &str = r#"
+        var a = 1, b, c = "tab\there \${not} ${a + 1}";
+        const limit = 10;
+        fun add(x, y = x * 2) { x + y }
+        fun early(n) { if (n > 1) return "big"; else if (n == 1) return "one"; return nil; }
+        if (a < 2 and !(b ?? false)) print a, b; else { print c; }
+        while (a < limit) a = a + 1;
+        do { a = a - 1; } while (a > 5);
+        outer: for (var i = 0; i < 3; i = i + 1) {
+            for (;;) { if (i == 1) continue outer; break; }
+        }
+        repeat (2) print "twice";
+        switch (a) {
+            case 1: print "one"; break;
+            case 2: print "two";
+            default: print "other";
+        }
+        try { throw "oops"; } catch (err) { print err; }
+        b or= 3;
+        c and= { var d = -a; d * 2 };
+        print a is number, add(1), -(1 + 2) / 3;
+    "#;
+
+    #[test]
+    fn formatted_source_parses_back_to_the_same_tree() {
+        let stmts = parse(PROGRAM).unwrap();
+        let formatted = Formatter::format(&stmts);
+        let reparsed = parse(&formatted).unwrap_or_else(|err| panic!("{err}\n{formatted}"));
+        assert_eq!(without_positions(&reparsed), without_positions(&stmts));
+    }
+
+    #[test]
+    fn formatting_is_stable() {
+        let formatted = Formatter::format(&parse(PROGRAM).unwrap());
+        assert_eq!(Formatter::format(&parse(&formatted).unwrap()), formatted);
+    }
+}
@@ -1,132 +1,593 @@
-use crate::stmt::FunctionDeclaration;
+use crate::stmt::{FunctionDeclaration, SwitchCase};
 use crate::Result;
 use crate::{
-    expr::{self, Expr},
+    expr::{self, Expr, Literal},
+    scanner::escape,
     stmt::{self, Stmt},
     token::{Token, TokenType},
-    Error,
+    Error, RuntimeErrorKind,
 };
+use base64::prelude::{Engine, BASE64_STANDARD};
 use derive_more::Display;
 use std::cell::RefCell;
-use std::collections::HashMap;
+use std::cmp::Ordering;
+use std::collections::{HashMap, HashSet};
+use std::fs;
+use std::num::NonZeroUsize;
 use std::rc::Rc;
 use std::time::SystemTime;
 
 trait Callable {
     fn call(&self, interpreter: &mut Interpreter, args: &[Value], paren: &Token) -> Result<Value>;
-    fn arity(&self) -> usize;
+    fn arity(&self) -> Arity;
+    fn name(&self) -> &str;
 }
 
-#[derive(PartialEq, Debug, Clone)]
+// Defaults make the arity a range, and variadic natives have no upper bound.
+#[derive(PartialEq, Debug, Clone, Copy)]
+pub struct Arity {
+    min: usize,
+    max: Option<usize>,
+}
+
+impl Arity {
+    pub fn exactly(count: usize) -> Self {
+        Self {
+            min: count,
+            max: Some(count),
+        }
+    }
+
+    pub fn between(min: usize, max: usize) -> Self {
+        Self {
+            min,
+            max: Some(max),
+        }
+    }
+
+    pub fn at_least(count: usize) -> Self {
+        Self {
+            min: count,
+            max: None,
+        }
+    }
+
+    fn accepts(&self, count: usize) -> bool {
+        count >= self.min && self.max.is_none_or(|max| count <= max)
+    }
+}
+
+impl Display for Arity {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self.max {
+            Some(max) if max == self.min => write!(f, "{}", self.min),
+            Some(max) => write!(f, "{} to {}", self.min, max),
+            None => write!(f, "at least {}", self.min),
+        }
+    }
+}
+
+#[derive(Clone)]
 pub struct NativeFunction {
-    arity: usize,
+    arity: Arity,
     name: String,
-    function: fn(&mut Interpreter, &[Value]) -> Value,
+    function: NativeFn,
+    // Only `check` sets this, so its calls can be dropped when checks are off.
+    skippable: bool,
+}
+
+impl std::fmt::Debug for NativeFunction {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.debug_struct("NativeFunction")
+            .field("arity", &self.arity)
+            .field("name", &self.name)
+            .field("skippable", &self.skippable)
+            .finish_non_exhaustive()
+    }
 }
 
+// Failures are `Error::Custom`, which the call site turns into a runtime error.
+type NativeFn = Rc<dyn Fn(&mut Interpreter, &[Value]) -> Result<Value>>;
+pub type TraceFn = Box<dyn FnMut(&Stmt)>;
+
 impl Callable for NativeFunction {
-    fn call(&self, interpreter: &mut Interpreter, args: &[Value], _paren: &Token) -> Result<Value> {
-        Ok((self.function)(interpreter, args))
+    fn call(&self, interpreter: &mut Interpreter, args: &[Value], paren: &Token) -> Result<Value> {
+        (self.function)(interpreter, args).map_err(|err| err.at(RuntimeErrorKind::Native, paren))
     }
 
-    fn arity(&self) -> usize {
+    fn arity(&self) -> Arity {
         self.arity
     }
+
+    fn name(&self) -> &str {
+        &self.name
+    }
 }
 
-#[derive(PartialEq, Debug, Clone)]
+#[derive(Clone)]
 pub struct Function {
-    declaration: FunctionDeclaration,
+    declaration: Rc<FunctionDeclaration>,
     closure: Env,
 }
 
-impl Callable for Function {
-    fn call(&self, interpreter: &mut Interpreter, args: &[Value], paren: &Token) -> Result<Value> {
-        if args.len() != self.arity() {
-            return Err(Error::runtime(
-                paren,
-                &format!(
-                    "Expected {} arguments but got {} arguments",
-                    self.arity(),
-                    args.len(),
-                ),
-            ));
-        };
+// Skips the closure, which usually holds the function itself.
+impl std::fmt::Debug for Function {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.debug_struct("Function")
+            .field("name", &self.declaration.name.text)
+            .field("arity", &self.arity())
+            .finish_non_exhaustive()
+    }
+}
 
-        let mut scope = Environment::new(Some(self.closure.clone()));
-        self.declaration
-            .params
-            .iter()
-            .enumerate()
-            .for_each(|(i, param)| scope.define(param, args[i].clone()));
+impl Callable for Function {
+    fn call(&self, interpreter: &mut Interpreter, args: &[Value], _paren: &Token) -> Result<Value> {
+        let scope = Environment::new(Some(self.closure.clone()));
+        let result = interpreter.with_env(scope, |interpreter| {
+            // Defaults can refer to earlier parameters.
+            for (i, param) in self.declaration.params.iter().enumerate() {
+                let value = match (args.get(i), &param.default) {
+                    (Some(arg), _) => arg.clone(),
+                    (None, Some(default)) => interpreter.evaluate(default)?,
+                    (None, None) => unreachable!("arity is checked before calling"),
+                };
+                interpreter.env.borrow_mut().define(&param.name.text, value);
+            }
+            // A trailing expression statement is the implicit return value.
+            match self.declaration.body.split_last() {
+                Some((last @ Stmt::Expression { expr }, stmts)) => {
+                    stmts
+                        .iter()
+                        .try_for_each(|stmt| interpreter.execute(stmt))?;
+                    interpreter.trace(last);
+                    interpreter.evaluate(expr)
+                }
+                _ => {
+                    let stmts = &self.declaration.body;
+                    stmts
+                        .iter()
+                        .try_for_each(|stmt| interpreter.execute(stmt))?;
+                    Ok(Value::Nil)
+                }
+            }
+        });
 
-        match interpreter.execute_block(&self.declaration.body, scope) {
+        match result {
             Err(Error::Return { value, .. }) => Ok(value),
-            _ => Ok(Value::Nil),
+            result => result,
+        }
+    }
+
+    fn arity(&self) -> Arity {
+        let params = &self.declaration.params;
+        Arity {
+            min: params
+                .iter()
+                .filter(|param| param.default.is_none())
+                .count(),
+            max: Some(params.len()),
         }
     }
 
-    fn arity(&self) -> usize {
-        self.declaration.params.len()
+    fn name(&self) -> &str {
+        &self.declaration.name.text
     }
 }
 
-#[derive(PartialEq, Debug, Clone)]
+#[derive(Debug, Clone)]
 pub enum Value {
     String(String),
     Number(f64),
     Boolean(bool),
+    Bytes(Vec<u8>),
     Function(Function),
     NativeFunction(NativeFunction),
     Nil,
+    // Bound by `var x;` in strict mode, where reading it is an error.
+    Undefined,
+}
+
+// NaN is never equal, 0 == -0, and functions are only equal to themselves.
+impl PartialEq for Value {
+    fn eq(&self, other: &Self) -> bool {
+        match (self, other) {
+            (Value::String(left), Value::String(right)) => left == right,
+            (Value::Number(left), Value::Number(right)) => left == right,
+            (Value::Boolean(left), Value::Boolean(right)) => left == right,
+            (Value::Bytes(left), Value::Bytes(right)) => left == right,
+            (Value::Function(left), Value::Function(right)) => {
+                Rc::ptr_eq(&left.declaration, &right.declaration)
+            }
+            (Value::NativeFunction(left), Value::NativeFunction(right)) => {
+                Rc::ptr_eq(&left.function, &right.function)
+            }
+            (Value::Nil, Value::Nil) => true,
+            _ => false,
+        }
+    }
+}
+
+// What `is` accepts on its right-hand side.
+const TYPE_NAMES: [&str; 6] = ["nil", "boolean", "number", "string", "bytes", "function"];
+
+impl Value {
+    pub fn type_name(&self) -> &'static str {
+        match self {
+            Value::String(_) => "string",
+            Value::Number(_) => "number",
+            Value::Boolean(_) => "boolean",
+            Value::Bytes(_) => "bytes",
+            Value::Function(_) | Value::NativeFunction(_) => "function",
+            Value::Nil => "nil",
+            Value::Undefined => "undefined",
+        }
+    }
+
+    pub fn is_number(&self) -> bool {
+        matches!(self, Value::Number(_))
+    }
+
+    pub fn is_string(&self) -> bool {
+        matches!(self, Value::String(_))
+    }
+
+    pub fn is_callable(&self) -> bool {
+        matches!(self, Value::Function(_) | Value::NativeFunction(_))
+    }
+
+    // Anything but an integral number in i64's range is an error.
+    pub fn as_integer(&self) -> Result<i64> {
+        let num = match *self {
+            Value::Number(num) if num.is_finite() && num.fract() == 0.0 => num,
+            _ => {
+                return Err(Error::custom(format!(
+                    "Expected an integer but got {}",
+                    self.repr()
+                )))
+            }
+        };
+        // i64::MAX as f64 rounds up to 2^63.
+        if num < i64::MIN as f64 || num >= i64::MAX as f64 {
+            return Err(Error::custom(format!(
+                "Integer {} is out of range",
+                self.repr()
+            )));
+        }
+        Ok(num as i64)
+    }
+
+    pub fn as_index(&self) -> Result<usize> {
+        let integer = self.as_integer()?;
+        usize::try_from(integer).map_err(|_| {
+            Error::custom(format!(
+                "Expected a non-negative integer but got {}",
+                integer
+            ))
+        })
+    }
+
+    // Strings come out quoted and escaped, so they read back as the same value.
+    pub fn repr(&self) -> String {
+        match self {
+            Value::String(string) => format!("\"{}\"", escape(string)),
+            Value::Nil => "nil".to_string(),
+            value => value.to_string(),
+        }
+    }
 }
 
 impl Display for Value {
     fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
         match self {
             Value::String(string) => write!(f, "{}", string),
-            Value::Number(num) => write!(f, "{}", num),
+            Value::Number(num) => write!(f, "{}", format_number(*num)),
             Value::Boolean(boolean) => write!(f, "{}", boolean),
-            Value::Function(Function {
-                declaration: FunctionDeclaration { name, .. },
-                ..
-            }) => write!(f, "<function {}>", name.text),
+            Value::Bytes(bytes) => {
+                write!(f, "<bytes")?;
+                for byte in bytes {
+                    write!(f, " {:02x}", byte)?;
+                }
+                write!(f, ">")
+            }
+            Value::Function(Function { declaration, .. }) => {
+                write!(f, "<function {}>", declaration.name.text)
+            }
             Value::NativeFunction(NativeFunction { name, .. }) => {
                 write!(f, "<native function {}>", name)
             }
             Value::Nil => write!(f, "null"),
+            Value::Undefined => write!(f, "undefined"),
         }
     }
 }
 
+// Matches JavaScript's Number#toString, as reference Lox does.
+fn format_number(num: f64) -> String {
+    if num.is_nan() {
+        return "NaN".to_string();
+    }
+    if num.is_infinite() {
+        return if num > 0.0 { "Infinity" } else { "-Infinity" }.to_string();
+    }
+    // Also covers -0.
+    if num == 0.0 {
+        return "0".to_string();
+    }
+
+    let scientific = format!("{:e}", num.abs());
+    let (mantissa, exponent) = scientific
+        .split_once('e')
+        .expect("Scientific notation always has an exponent");
+    let digits = mantissa.replace('.', "");
+    let exponent: i32 = exponent.parse().expect("Exponent is always an integer");
+    let sign = if num < 0.0 { "-" } else { "" };
+
+    let point = exponent + 1;
+    let len = digits.len() as i32;
+    let formatted = if len <= point && point <= 21 {
+        format!("{}{}", digits, "0".repeat((point - len) as usize))
+    } else if 0 < point && point <= 21 {
+        let (whole, fraction) = digits.split_at(point as usize);
+        format!("{}.{}", whole, fraction)
+    } else if -6 < point && point <= 0 {
+        format!("0.{}{}", "0".repeat(-point as usize), digits)
+    } else {
+        let (first, rest) = digits.split_at(1);
+        let exponent_sign = if exponent < 0 { "-" } else { "+" };
+        if rest.is_empty() {
+            format!("{}e{}{}", first, exponent_sign, exponent.abs())
+        } else {
+            format!("{}.{}e{}{}", first, rest, exponent_sign, exponent.abs())
+        }
+    };
+    format!("{}{}", sign, formatted)
+}
+
 pub struct Interpreter {
     env: Env,
+    trace: Option<TraceFn>,
+    // Shown under runtime errors.
+    source: Vec<String>,
+    // Reading an unassigned `var x;` is an error rather than nil.
+    strict: bool,
+    debug_values: bool,
+    trace_calls: bool,
+    call_depth: usize,
+    // Significant digits for displayed numbers. None is the shortest form.
+    precision: Option<NonZeroUsize>,
+    // When false, `check` calls are skipped without evaluating their arguments.
+    checks: bool,
 }
 
 impl Interpreter {
     pub fn new() -> Self {
-        let mut env = Environment::default();
-        env.store.insert(
-            "clock".to_string(),
-            Value::NativeFunction(NativeFunction {
-                arity: 0,
-                name: "clock".to_string(),
-                function: |_interpreter, _args| {
-                    Value::Number(
-                        SystemTime::now()
-                            .duration_since(SystemTime::UNIX_EPOCH)
-                            .expect("Clock may have gone backwards")
-                            .as_millis() as f64
-                            / 1000.0,
-                    )
-                },
-            }),
-        );
+        let mut interpreter = Self::bare();
+        interpreter.define_stdlib();
+        interpreter
+    }
+
+    // No natives at all, not even `clock`.
+    pub fn bare() -> Self {
         Self {
-            env: Rc::new(RefCell::new(env)),
+            env: Rc::new(RefCell::new(Environment::default())),
+            trace: None,
+            source: vec![],
+            strict: false,
+            debug_values: false,
+            trace_calls: false,
+            call_depth: 0,
+            precision: None,
+            checks: true,
         }
     }
 
+    fn define_stdlib(&mut self) {
+        let mut env = self.env.borrow_mut();
+        env.define_native("clock", Arity::exactly(0), |_interpreter, _args| {
+            Ok(Value::Number(
+                SystemTime::now()
+                    .duration_since(SystemTime::UNIX_EPOCH)
+                    .expect("Clock may have gone backwards")
+                    .as_millis() as f64
+                    / 1000.0,
+            ))
+        });
+        env.define_native("repr", Arity::exactly(1), |_interpreter, args| {
+            Ok(Value::String(args[0].repr()))
+        });
+        env.define_native("contains", Arity::exactly(2), |_interpreter, args| {
+            match (&args[0], &args[1]) {
+                (Value::String(string), Value::String(substring)) => {
+                    Ok(Value::Boolean(string.contains(substring.as_str())))
+                }
+                (Value::String(_), item) => Err(Error::custom(format!(
+                    "Can only search a string for a string, got {}",
+                    item.repr()
+                ))),
+                (collection, _) => Err(Error::custom(format!(
+                    "Can only check containment in strings, got {}",
+                    collection.repr()
+                ))),
+            }
+        });
+        env.define_native("format", Arity::at_least(1), |interpreter, args| {
+            let Value::String(template) = &args[0] else {
+                return Err(Error::custom(format!(
+                    "Format string must be a string, got {}",
+                    args[0].repr()
+                )));
+            };
+            format(interpreter, template, &args[1..]).map(Value::String)
+        });
+        // There is no list value yet, so the names are joined into one string.
+        env.define_native("vars", Arity::exactly(0), |interpreter, _args| {
+            Ok(Value::String(
+                RefCell::borrow(&interpreter.env).names().join(", "),
+            ))
+        });
+        env.define_native("slice", Arity::between(2, 3), |_interpreter, args| {
+            let Value::String(string) = &args[0] else {
+                return Err(Error::custom(format!(
+                    "Can only slice strings, got {}",
+                    args[0].repr()
+                )));
+            };
+            let chars: Vec<char> = string.chars().collect();
+            let start = slice_bound(&args[1], chars.len())?;
+            let end = match args.get(2) {
+                Some(end) => slice_bound(end, chars.len())?,
+                None => chars.len(),
+            };
+            let slice = chars.get(start..end).unwrap_or_default();
+            Ok(Value::String(slice.iter().collect()))
+        });
+        env.define_native("read_file", Arity::exactly(1), |_interpreter, args| {
+            let path = path_argument(&args[0])?;
+            fs::read_to_string(path)
+                .map(Value::String)
+                .map_err(|err| Error::custom(format!("Could not read '{path}': {err}")))
+        });
+        env.define_native("write_file", Arity::exactly(2), |_interpreter, args| {
+            let path = path_argument(&args[0])?;
+            let Value::String(contents) = &args[1] else {
+                return Err(Error::custom(format!(
+                    "File contents must be a string, got {}",
+                    args[1].repr()
+                )));
+            };
+            fs::write(path, contents)
+                .map(|_| Value::Nil)
+                .map_err(|err| Error::custom(format!("Could not write '{path}': {err}")))
+        });
+        // Halfway cases round away from zero, so round(2.5) is 3 and round(-2.5) is -3.
+        env.define_native("round", Arity::between(1, 2), |_interpreter, args| {
+            if !args[0].is_number() {
+                return Err(Error::custom(format!(
+                    "Can only round a number, got {}",
+                    args[0].repr()
+                )));
+            }
+            let Value::Number(num) = args[0] else {
+                unreachable!("argument type is checked above")
+            };
+            let digits = match args.get(1) {
+                Some(digits) => digits.as_index()?,
+                None => 0,
+            };
+            let scale = 10f64.powf(digits as f64);
+            let scaled = num * scale;
+            if !scaled.is_finite() {
+                return Ok(Value::Number(num));
+            }
+            Ok(Value::Number(scaled.round() / scale))
+        });
+        env.define_native("builtins", Arity::exactly(0), |interpreter, _args| {
+            Ok(Value::String(
+                RefCell::borrow(&interpreter.env).native_names().join(", "),
+            ))
+        });
+        // Inclusive at both ends.
+        env.define_native("inRange", Arity::exactly(3), |_interpreter, args| {
+            if !args.iter().all(Value::is_number) {
+                return Err(Error::custom(format!(
+                    "inRange expects three numbers, got {}, {} and {}",
+                    args[0].repr(),
+                    args[1].repr(),
+                    args[2].repr()
+                )));
+            }
+            match args {
+                [Value::Number(x), Value::Number(lo), Value::Number(hi)] => {
+                    Ok(Value::Boolean(lo <= x && x <= hi))
+                }
+                _ => unreachable!("argument types are checked above"),
+            }
+        });
+        // setPrecision(0) goes back to the default shortest form.
+        env.define_native("setPrecision", Arity::exactly(1), |interpreter, args| {
+            interpreter.set_precision(NonZeroUsize::new(args[0].as_index()?));
+            Ok(Value::Nil)
+        });
+        env.define_skippable_native("check", Arity::between(1, 2), |interpreter, args| {
+            if interpreter.is_truthy(&args[0]) {
+                return Ok(Value::Nil);
+            }
+            match args.get(1) {
+                Some(msg) => Err(Error::custom(format!(
+                    "Check failed: {}",
+                    interpreter.display(msg)
+                ))),
+                None => Err(Error::custom("Check failed")),
+            }
+        });
+        env.define_native("min", Arity::at_least(1), |_interpreter, args| {
+            args[1..].iter().try_fold(args[0].clone(), |min, arg| {
+                extreme(&min, arg, Ordering::Less)
+            })
+        });
+        env.define_native("max", Arity::at_least(1), |_interpreter, args| {
+            args[1..].iter().try_fold(args[0].clone(), |max, arg| {
+                extreme(&max, arg, Ordering::Greater)
+            })
+        });
+        // Text that isn't a number gives nil.
+        env.define_native("parseInt", Arity::between(1, 2), |_interpreter, args| {
+            let text = parse_argument(&args[0])?;
+            let radix = match args.get(1) {
+                Some(radix) => match radix.as_integer()? {
+                    radix @ 2..=36 => radix as u32,
+                    radix => {
+                        return Err(Error::custom(format!(
+                            "Radix must be from 2 to 36, got {radix}"
+                        )))
+                    }
+                },
+                None => 10,
+            };
+            Ok(parse_int(text.trim(), radix).map_or(Value::Nil, Value::Number))
+        });
+        env.define_native("parseFloat", Arity::exactly(1), |_interpreter, args| {
+            let text = parse_argument(&args[0])?;
+            Ok(text
+                .trim()
+                .parse()
+                .ok()
+                .filter(|num: &f64| num.is_finite())
+                .map_or(Value::Nil, Value::Number))
+        });
+        env.define_native("toBytes", Arity::exactly(1), |_interpreter, args| {
+            let Value::String(string) = &args[0] else {
+                return Err(Error::custom(format!(
+                    "Can only encode a string to bytes, got {}",
+                    args[0].repr()
+                )));
+            };
+            Ok(Value::Bytes(string.as_bytes().to_vec()))
+        });
+        env.define_native("fromBytes", Arity::exactly(1), |_interpreter, args| {
+            let bytes = bytes_argument(&args[0])?;
+            String::from_utf8(bytes.to_vec())
+                .map(Value::String)
+                .map_err(|err| Error::custom(format!("Bytes are not valid UTF-8: {err}")))
+        });
+        env.define_native("base64Encode", Arity::exactly(1), |_interpreter, args| {
+            let bytes = bytes_argument(&args[0])?;
+            Ok(Value::String(BASE64_STANDARD.encode(bytes)))
+        });
+        env.define_native("base64Decode", Arity::exactly(1), |_interpreter, args| {
+            let Value::String(string) = &args[0] else {
+                return Err(Error::custom(format!(
+                    "Can only decode a string from base64, got {}",
+                    args[0].repr()
+                )));
+            };
+            BASE64_STANDARD
+                .decode(string)
+                .map(Value::Bytes)
+                .map_err(|err| Error::custom(format!("Invalid base64: {err}")))
+        });
+    }
+
     pub fn interpret(&mut self, stmts: Vec<Stmt>) -> Result<()> {
         for stmt in stmts {
             self.execute(&stmt)?
@@ -134,34 +595,186 @@ impl Interpreter {
         Ok(())
     }
 
+    pub fn interpret_expression(&mut self, expr: &Expr) -> Result<Value> {
+        self.evaluate(expr)
+    }
+
+    // Called before every statement, including nested ones.
+    pub fn set_trace(&mut self, trace: TraceFn) {
+        self.trace = Some(trace);
+    }
+
+    // Registers a native in the current scope, the globals unless called mid-run.
+    pub fn define_native(
+        &mut self,
+        name: &str,
+        arity: Arity,
+        function: impl Fn(&mut Interpreter, &[Value]) -> Result<Value> + 'static,
+    ) {
+        self.env.borrow_mut().define_native(name, arity, function);
+    }
+
+    // Binds a variable in the current scope, the globals unless called mid-run.
+    pub fn define(&mut self, name: &str, value: Value) {
+        self.env.borrow_mut().define(name, value);
+    }
+
+    pub fn set_strict(&mut self, strict: bool) {
+        self.strict = strict;
+    }
+
+    pub fn set_debug_values(&mut self, debug_values: bool) {
+        self.debug_values = debug_values;
+    }
+
+    pub fn set_checks(&mut self, checks: bool) {
+        self.checks = checks;
+    }
+
+    pub fn set_trace_calls(&mut self, trace_calls: bool) {
+        self.trace_calls = trace_calls;
+    }
+
+    pub fn set_precision(&mut self, precision: Option<NonZeroUsize>) {
+        self.precision = precision;
+    }
+
+    // Display form with numbers rounded to the configured precision.
+    pub fn display(&self, value: &Value) -> String {
+        match (value, self.precision) {
+            (Value::Number(num), Some(precision)) if num.is_finite() => {
+                let rounded: f64 = format!("{:.*e}", precision.get().min(17) - 1, num)
+                    .parse()
+                    .expect("Formatted float always parses");
+                format_number(rounded)
+            }
+            _ => value.to_string(),
+        }
+    }
+
+    pub fn set_source(&mut self, source: &str) {
+        self.source = source.lines().map(str::to_string).collect();
+    }
+
+    // Lines are numbered from 1, as in tokens and errors.
+    pub fn source_line(&self, line: usize) -> Option<&str> {
+        let index = line.checked_sub(1)?;
+        self.source.get(index).map(String::as_str)
+    }
+
+    fn trace(&mut self, stmt: &Stmt) {
+        if let Some(trace) = &mut self.trace {
+            trace(stmt);
+        }
+    }
+
     fn execute(&mut self, stmt: &Stmt) -> Result<()> {
+        self.trace(stmt);
         stmt.accept(self)
     }
 
     fn execute_block(&mut self, stmts: &[Stmt], env: Environment) -> Result<()> {
-        let previous = Rc::clone(&self.env);
-        self.env = Rc::new(RefCell::new(env));
-        let result = stmts.iter().try_for_each(|stmt| self.execute(stmt));
-        self.env = previous;
-        result
+        self.with_env(env, |interpreter| {
+            stmts.iter().try_for_each(|stmt| interpreter.execute(stmt))
+        })
+    }
+
+    fn with_env<T>(
+        &mut self,
+        env: Environment,
+        run: impl FnOnce(&mut Self) -> Result<T>,
+    ) -> Result<T> {
+        let mut guard = EnvGuard::new(self, env);
+        run(&mut guard)
+    }
+
+    // Runs one iteration of a loop body, returning false if the loop was broken out of.
+    fn execute_loop_body(&mut self, body: &Stmt, label: Option<&Token>) -> Result<bool> {
+        match self.execute(body) {
+            Err(Error::Break { label: target, .. }) if targets_loop(&target, label) => Ok(false),
+            Err(Error::Continue { label: target, .. }) if targets_loop(&target, label) => Ok(true),
+            result => result.map(|_| true),
+        }
     }
 
     fn evaluate(&mut self, expr: &Expr) -> Result<Value> {
-        expr.accept(self)
+        use expr::Visitor;
+        match expr {
+            Expr::Literal { value } => self.visit_literal(value),
+            Expr::Unary { operator, right } => self.visit_unary(operator, right),
+            Expr::Binary {
+                left,
+                operator,
+                right,
+            } => self.visit_binary(left, operator, right),
+            Expr::Call {
+                callee,
+                paren,
+                args,
+            } => self.visit_call(callee, paren, args),
+            Expr::Interpolation { parts } => self.visit_interpolation(parts),
+            Expr::TypeTest {
+                value, type_name, ..
+            } => self.visit_type_test(value, type_name),
+            Expr::Grouping { expr } => self.evaluate(expr),
+            Expr::Variable { name } => self.visit_variable(name),
+            Expr::Assignment { name, value } => self.visit_assignment(name, value),
+            Expr::LogicOr { left, right, .. } => self.visit_logic_or(left, right),
+            Expr::LogicAnd { left, right, .. } => self.visit_logic_and(left, right),
+            Expr::Coalesce { left, right, .. } => self.visit_coalesce(left, right),
+            Expr::Block { stmts, value } => self.visit_block_expression(stmts, value.as_deref()),
+        }
     }
 
     fn is_truthy(&self, value: &Value) -> bool {
         match *value {
             Value::String(_) => true,
+            Value::Bytes(_) => true,
             Value::Number(_) => true,
             Value::Boolean(v) => v,
             Value::Function(_) => false,
             Value::NativeFunction(_) => false,
-            Value::Nil => false,
+            Value::Nil | Value::Undefined => false,
         }
     }
 }
 
+// Restores the enclosing environment when dropped, however the block is left.
+struct EnvGuard<'a> {
+    interpreter: &'a mut Interpreter,
+    previous: Env,
+}
+
+impl<'a> EnvGuard<'a> {
+    fn new(interpreter: &'a mut Interpreter, env: Environment) -> Self {
+        let previous = std::mem::replace(&mut interpreter.env, Rc::new(RefCell::new(env)));
+        EnvGuard {
+            interpreter,
+            previous,
+        }
+    }
+}
+
+impl Drop for EnvGuard<'_> {
+    fn drop(&mut self) {
+        self.interpreter.env = Rc::clone(&self.previous);
+    }
+}
+
+impl std::ops::Deref for EnvGuard<'_> {
+    type Target = Interpreter;
+
+    fn deref(&self) -> &Interpreter {
+        self.interpreter
+    }
+}
+
+impl std::ops::DerefMut for EnvGuard<'_> {
+    fn deref_mut(&mut self) -> &mut Interpreter {
+        self.interpreter
+    }
+}
+
 impl Default for Interpreter {
     fn default() -> Self {
         Self::new()
@@ -171,15 +784,13 @@ impl Default for Interpreter {
 impl expr::Visitor for Interpreter {
     type Out = Result<Value>;
 
-    fn visit_literal(&mut self, value: &Token) -> Self::Out {
-        match &value.token_type {
-            TokenType::Nil => Ok(Value::Nil),
-            TokenType::False => Ok(Value::Boolean(false)),
-            TokenType::True => Ok(Value::Boolean(true)),
-            TokenType::Number(n) => Ok(Value::Number(*n)),
-            TokenType::String(s) => Ok(Value::String(s.clone())),
-            _ => Err(Error::runtime(value, "Unknown literal type")),
-        }
+    fn visit_literal(&mut self, value: &Literal) -> Self::Out {
+        Ok(match value {
+            Literal::Number(num) => Value::Number(*num),
+            Literal::String(string) => Value::String(string.clone()),
+            Literal::Boolean(boolean) => Value::Boolean(*boolean),
+            Literal::Nil => Value::Nil,
+        })
     }
 
     fn visit_unary(&mut self, operator: &Token, right: &Expr) -> Self::Out {
@@ -188,28 +799,44 @@ impl expr::Visitor for Interpreter {
         match operator.token_type {
             TokenType::Minus => match right {
                 Value::Number(value) => Ok(Value::Number(-value)),
-                _ => Err(Error::runtime(operator, "Operand must be a number")),
+                _ => Err(Error::runtime(
+                    RuntimeErrorKind::TypeMismatch,
+                    operator,
+                    "Operand must be a number",
+                )),
             },
             TokenType::Bang => Ok(Value::Boolean(self.is_truthy(&right))),
-            _ => Err(Error::runtime(operator, "Unknown unary operator")),
+            _ => Err(Error::runtime(
+                RuntimeErrorKind::UnknownOperator,
+                operator,
+                "Unknown unary operator",
+            )),
         }
     }
 
     fn visit_call(&mut self, callee: &Expr, paren: &Token, args: &[Expr]) -> Self::Out {
         let callee = self.evaluate(callee)?;
+        if !self.checks && matches!(&callee, Value::NativeFunction(f) if f.skippable) {
+            return Ok(Value::Nil);
+        }
         let args = args
             .iter()
             .map(|arg| self.evaluate(arg))
             .collect::<Result<Vec<_>>>()?;
-        let callable: Box<dyn Callable> = match callee {
-            Value::NativeFunction(f) => Box::new(f),
-            Value::Function(f) => Box::new(f),
+        let callable: &dyn Callable = match &callee {
+            Value::NativeFunction(f) => f,
+            Value::Function(f) => f,
             _ => {
-                return Err(Error::runtime(paren, "Can only call functions and classes"));
+                return Err(Error::runtime(
+                    RuntimeErrorKind::NotCallable,
+                    paren,
+                    "Can only call functions and classes",
+                ));
             }
         };
-        if args.len() != callable.arity() {
+        if !callable.arity().accepts(args.len()) {
             return Err(Error::runtime(
+                RuntimeErrorKind::Arity,
                 paren,
                 &format!(
                     "Expected {} arguments but got {} arguments",
@@ -218,7 +845,42 @@ impl expr::Visitor for Interpreter {
                 ),
             ));
         };
-        callable.call(self, &args, paren)
+        if !self.trace_calls {
+            return callable.call(self, &args, paren);
+        }
+
+        let indent = "  ".repeat(self.call_depth);
+        let shown: Vec<String> = args.iter().map(Value::repr).collect();
+        eprintln!("{indent}call {}({})", callable.name(), shown.join(", "));
+        self.call_depth += 1;
+        let result = callable.call(self, &args, paren);
+        self.call_depth -= 1;
+        match &result {
+            Ok(value) => eprintln!("{indent}return {}", value.repr()),
+            Err(_) => eprintln!("{indent}unwind {}", callable.name()),
+        }
+        result
+    }
+
+    fn visit_interpolation(&mut self, parts: &[Expr]) -> Self::Out {
+        let mut result = String::new();
+        for part in parts {
+            let value = self.evaluate(part)?;
+            result.push_str(&self.display(&value));
+        }
+        Ok(Value::String(result))
+    }
+
+    fn visit_type_test(&mut self, value: &Expr, type_name: &Token) -> Self::Out {
+        let value = self.evaluate(value)?;
+        if !TYPE_NAMES.contains(&type_name.text.as_str()) {
+            return Err(Error::runtime(
+                RuntimeErrorKind::InvalidValue,
+                type_name,
+                &format!("Unknown type '{}'", type_name.text),
+            ));
+        }
+        Ok(Value::Boolean(value.type_name() == type_name.text))
     }
 
     fn visit_grouping(&mut self, expr: &Expr) -> Self::Out {
@@ -232,47 +894,88 @@ impl expr::Visitor for Interpreter {
         match operator.token_type {
             TokenType::Minus => match (left, right) {
                 (Value::Number(left), Value::Number(right)) => Ok(Value::Number(left - right)),
-                _ => Err(Error::runtime(operator, "Operands must be numbers")),
+                _ => Err(Error::runtime(
+                    RuntimeErrorKind::TypeMismatch,
+                    operator,
+                    "Operands must be numbers",
+                )),
             },
             TokenType::Plus => match (left, right) {
                 (Value::Number(left), Value::Number(right)) => Ok(Value::Number(left + right)),
                 (Value::String(left), Value::String(right)) => Ok(Value::String(left + &right)),
                 _ => Err(Error::runtime(
+                    RuntimeErrorKind::TypeMismatch,
                     operator,
                     "Operands must be numbers or strings",
                 )),
             },
             TokenType::Star => match (left, right) {
                 (Value::Number(left), Value::Number(right)) => Ok(Value::Number(left * right)),
-                _ => Err(Error::runtime(operator, "Operands must be numbers")),
+                _ => Err(Error::runtime(
+                    RuntimeErrorKind::TypeMismatch,
+                    operator,
+                    "Operands must be numbers",
+                )),
             },
             TokenType::Slash => match (left, right) {
                 (Value::Number(left), Value::Number(right)) => Ok(Value::Number(left / right)),
-                _ => Err(Error::runtime(operator, "Operands must be numbers")),
+                _ => Err(Error::runtime(
+                    RuntimeErrorKind::TypeMismatch,
+                    operator,
+                    "Operands must be numbers",
+                )),
             },
             TokenType::Greater => match (left, right) {
                 (Value::Number(left), Value::Number(right)) => Ok(Value::Boolean(left > right)),
-                _ => Err(Error::runtime(operator, "Operands must be numbers")),
+                _ => Err(Error::runtime(
+                    RuntimeErrorKind::TypeMismatch,
+                    operator,
+                    "Operands must be numbers",
+                )),
             },
             TokenType::GreaterEqual => match (left, right) {
                 (Value::Number(left), Value::Number(right)) => Ok(Value::Boolean(left >= right)),
-                _ => Err(Error::runtime(operator, "Operands must be numbers")),
+                _ => Err(Error::runtime(
+                    RuntimeErrorKind::TypeMismatch,
+                    operator,
+                    "Operands must be numbers",
+                )),
             },
             TokenType::Less => match (left, right) {
                 (Value::Number(left), Value::Number(right)) => Ok(Value::Boolean(left < right)),
-                _ => Err(Error::runtime(operator, "Operands must be numbers")),
+                _ => Err(Error::runtime(
+                    RuntimeErrorKind::TypeMismatch,
+                    operator,
+                    "Operands must be numbers",
+                )),
             },
             TokenType::LessEqual => match (left, right) {
                 (Value::Number(left), Value::Number(right)) => Ok(Value::Boolean(left <= right)),
-                _ => Err(Error::runtime(operator, "Operands must be numbers")),
+                _ => Err(Error::runtime(
+                    RuntimeErrorKind::TypeMismatch,
+                    operator,
+                    "Operands must be numbers",
+                )),
             },
             TokenType::EqualEqual => Ok(Value::Boolean(left == right)),
-            _ => Err(Error::runtime(operator, "Unknown binary operator")),
+            TokenType::BangEqual => Ok(Value::Boolean(left != right)),
+            _ => Err(Error::runtime(
+                RuntimeErrorKind::UnknownOperator,
+                operator,
+                "Unknown binary operator",
+            )),
         }
     }
 
     fn visit_variable(&mut self, name: &Token) -> Self::Out {
-        self.env.borrow().get(name)
+        match self.env.borrow().get(name)? {
+            Value::Undefined => Err(Error::runtime(
+                RuntimeErrorKind::UnassignedVariable,
+                name,
+                &format!("Variable '{}' is used before being assigned", name.text),
+            )),
+            value => Ok(value),
+        }
     }
 
     fn visit_assignment(&mut self, name: &Token, value: &Expr) -> Self::Out {
@@ -297,6 +1000,27 @@ impl expr::Visitor for Interpreter {
             Ok(value)
         }
     }
+
+    // Unlike `or`, only nil falls through to the right-hand side; `false` is kept.
+    fn visit_coalesce(&mut self, left: &Expr, right: &Expr) -> Self::Out {
+        match self.evaluate(left)? {
+            Value::Nil => self.evaluate(right),
+            value => Ok(value),
+        }
+    }
+
+    fn visit_block_expression(&mut self, stmts: &[Stmt], value: Option<&Expr>) -> Self::Out {
+        let environment = Environment::new(Some(Rc::clone(&self.env)));
+        self.with_env(environment, |interpreter| {
+            for stmt in stmts {
+                interpreter.execute(stmt)?;
+            }
+            match value {
+                Some(value) => interpreter.evaluate(value),
+                None => Ok(Value::Nil),
+            }
+        })
+    }
 }
 
 impl stmt::Visitor for Interpreter {
@@ -307,9 +1031,19 @@ impl stmt::Visitor for Interpreter {
         Ok(())
     }
 
-    fn visit_print(&mut self, expr: &Expr) -> Self::Out {
-        let value = self.evaluate(expr)?;
-        println!("{value}");
+    fn visit_print(&mut self, exprs: &[Expr]) -> Self::Out {
+        let values = exprs
+            .iter()
+            .map(|expr| {
+                let value = self.evaluate(expr)?;
+                Ok(if self.debug_values {
+                    format!("{value:?}")
+                } else {
+                    self.display(&value)
+                })
+            })
+            .collect::<Result<Vec<_>>>()?;
+        println!("{}", values.join(" "));
         Ok(())
     }
 
@@ -318,15 +1052,12 @@ impl stmt::Visitor for Interpreter {
         function_declaration: &FunctionDeclaration,
     ) -> Self::Out {
         let function = Function {
-            declaration: function_declaration.clone(),
+            declaration: Rc::new(function_declaration.clone()),
             closure: self.env.clone(),
         };
-        self.env.borrow_mut().define(
-            &function_declaration.name,
-            Value::Function(function).clone(),
-        );
-
-        Ok(())
+        self.env
+            .borrow_mut()
+            .declare(&function_declaration.name, Value::Function(function))
     }
 
     fn visit_return(&mut self, keyword: &Token, value: Option<&Expr>) -> Self::Out {
@@ -341,6 +1072,29 @@ impl stmt::Visitor for Interpreter {
         })
     }
 
+    fn visit_throw(&mut self, keyword: &Token, value: &Expr) -> Self::Out {
+        let value = self.evaluate(value)?;
+        Err(Error::runtime(
+            RuntimeErrorKind::Thrown,
+            keyword,
+            &value.to_string(),
+        ))
+    }
+
+    fn visit_break(&mut self, keyword: &Token, label: Option<&Token>) -> Self::Out {
+        Err(Error::Break {
+            line: keyword.line,
+            label: label.map(|label| label.text.clone()),
+        })
+    }
+
+    fn visit_continue(&mut self, keyword: &Token, label: Option<&Token>) -> Self::Out {
+        Err(Error::Continue {
+            line: keyword.line,
+            label: label.map(|label| label.text.clone()),
+        })
+    }
+
     fn visit_variable_declaration(
         &mut self,
         name: &Token,
@@ -348,10 +1102,15 @@ impl stmt::Visitor for Interpreter {
     ) -> Self::Out {
         let value = match initializer {
             Some(initializer) => self.evaluate(initializer)?,
+            None if self.strict => Value::Undefined,
             None => Value::Nil,
         };
-        RefCell::borrow_mut(&self.env).define(name, value);
-        Ok(())
+        RefCell::borrow_mut(&self.env).declare(name, value)
+    }
+
+    fn visit_const_declaration(&mut self, name: &Token, initializer: &Expr) -> Self::Out {
+        let value = self.evaluate(initializer)?;
+        RefCell::borrow_mut(&self.env).define_constant(name, value)
     }
 
     fn visit_block(&mut self, stmts: &[Stmt]) -> Self::Out {
@@ -360,6 +1119,22 @@ impl stmt::Visitor for Interpreter {
         Ok(())
     }
 
+    fn visit_try(&mut self, body: &[Stmt], error_name: &Token, handler: &[Stmt]) -> Self::Out {
+        let environment = Environment::new(Some(Rc::clone(&self.env)));
+        match self.execute_block(body, environment) {
+            Err(Error::RuntimeError { msg, .. }) => {
+                let mut environment = Environment::new(Some(Rc::clone(&self.env)));
+                environment.define(&error_name.text, Value::String(msg));
+                self.execute_block(handler, environment)
+            }
+            result => result,
+        }
+    }
+
+    fn visit_sequence(&mut self, stmts: &[Stmt]) -> Self::Out {
+        stmts.iter().try_for_each(|stmt| self.execute(stmt))
+    }
+
     fn visit_if(
         &mut self,
         condition: &Expr,
@@ -375,24 +1150,254 @@ impl stmt::Visitor for Interpreter {
         Ok(())
     }
 
-    fn visit_while(&mut self, condition: &Expr, body: &Stmt) -> Self::Out {
-        loop {
-            let condition_result = &self.evaluate(condition)?;
-            if !self.is_truthy(condition_result) {
+    fn visit_switch(
+        &mut self,
+        discriminant: &Expr,
+        cases: &[SwitchCase],
+        default: Option<&[Stmt]>,
+    ) -> Self::Out {
+        let discriminant = self.evaluate(discriminant)?;
+        let mut body = default;
+        for case in cases {
+            if self.evaluate(&case.value)? == discriminant {
+                body = Some(&case.body);
                 break;
             }
-            self.execute(body)?;
+        }
+        if let Some(body) = body {
+            let environment = Environment::new(Some(Rc::clone(&self.env)));
+            match self.execute_block(body, environment) {
+                Err(Error::Break { label: None, .. }) => {}
+                result => result?,
+            }
         }
         Ok(())
     }
-}
-
-type Env = Rc<RefCell<Environment>>;
 
-#[derive(Default, Debug, PartialEq)]
+    fn visit_while(&mut self, condition: &Expr, body: &Stmt, label: Option<&Token>) -> Self::Out {
+        loop {
+            let condition_result = &self.evaluate(condition)?;
+            if !self.is_truthy(condition_result) {
+                break;
+            }
+            if !self.execute_loop_body(body, label)? {
+                break;
+            }
+        }
+        Ok(())
+    }
+
+    fn visit_for(
+        &mut self,
+        initializer: Option<&Stmt>,
+        condition: Option<&Expr>,
+        increment: Option<&Expr>,
+        body: &Stmt,
+        label: Option<&Token>,
+    ) -> Self::Out {
+        let environment = Environment::new(Some(Rc::clone(&self.env)));
+        self.with_env(environment, |interpreter| {
+            if let Some(initializer) = initializer {
+                interpreter.execute(initializer)?;
+            }
+            loop {
+                if let Some(condition) = condition {
+                    let condition_result = &interpreter.evaluate(condition)?;
+                    if !interpreter.is_truthy(condition_result) {
+                        break;
+                    }
+                }
+                if !interpreter.execute_loop_body(body, label)? {
+                    break;
+                }
+                if let Some(increment) = increment {
+                    interpreter.evaluate(increment)?;
+                }
+            }
+            Ok(())
+        })
+    }
+
+    fn visit_repeat(
+        &mut self,
+        keyword: &Token,
+        count: &Expr,
+        body: &Stmt,
+        label: Option<&Token>,
+    ) -> Self::Out {
+        let count = self
+            .evaluate(count)?
+            .as_index()
+            .map_err(|err| err.at(RuntimeErrorKind::InvalidValue, keyword))?;
+        for _ in 0..count {
+            if !self.execute_loop_body(body, label)? {
+                break;
+            }
+        }
+        Ok(())
+    }
+
+    fn visit_do_while(
+        &mut self,
+        body: &Stmt,
+        condition: &Expr,
+        label: Option<&Token>,
+    ) -> Self::Out {
+        loop {
+            if !self.execute_loop_body(body, label)? {
+                break;
+            }
+            let condition_result = &self.evaluate(condition)?;
+            if !self.is_truthy(condition_result) {
+                break;
+            }
+        }
+        Ok(())
+    }
+}
+
+fn path_argument(path: &Value) -> Result<&str> {
+    match path {
+        Value::String(path) => Ok(path),
+        _ => Err(Error::custom(format!(
+            "File path must be a string, got {}",
+            path.repr()
+        ))),
+    }
+}
+
+fn bytes_argument(bytes: &Value) -> Result<&[u8]> {
+    match bytes {
+        Value::Bytes(bytes) => Ok(bytes),
+        _ => Err(Error::custom(format!(
+            "Expected bytes, got {}",
+            bytes.repr()
+        ))),
+    }
+}
+
+fn parse_argument(text: &Value) -> Result<&str> {
+    match text {
+        Value::String(text) => Ok(text),
+        _ => Err(Error::custom(format!(
+            "Can only parse a string, got {}",
+            text.repr()
+        ))),
+    }
+}
+
+// Falls back to f64 once the digits overflow a u128.
+fn parse_int(text: &str, radix: u32) -> Option<f64> {
+    let (sign, digits) = match text.strip_prefix('-') {
+        Some(digits) => (-1.0, digits),
+        None => (1.0, text.strip_prefix('+').unwrap_or(text)),
+    };
+    if digits.is_empty() {
+        return None;
+    }
+    let mut exact = Some(0u128);
+    let mut num = 0.0;
+    for c in digits.chars() {
+        let digit = c.to_digit(radix)?;
+        exact = exact
+            .and_then(|exact| exact.checked_mul(radix as u128))
+            .and_then(|exact| exact.checked_add(digit as u128));
+        num = match exact {
+            Some(exact) => exact as f64,
+            None => num * radix as f64 + digit as f64,
+        };
+    }
+    Some(sign * num)
+}
+
+// Negative bounds count from the end, and out-of-range bounds are clamped.
+fn slice_bound(bound: &Value, len: usize) -> Result<usize> {
+    let bound = bound.as_integer()?;
+    let len = i64::try_from(len).unwrap_or(i64::MAX);
+    let bound = if bound < 0 {
+        len.saturating_add(bound)
+    } else {
+        bound
+    };
+    Ok(bound.clamp(0, len) as usize)
+}
+
+// Whichever of `a` and `b` sorts towards `pick`, `a` on a tie.
+fn extreme(a: &Value, b: &Value, pick: Ordering) -> Result<Value> {
+    if !(a.is_number() && b.is_number() || a.is_string() && b.is_string()) {
+        return Err(Error::custom(format!(
+            "Can only compare two numbers or two strings, got {} and {}",
+            a.repr(),
+            b.repr()
+        )));
+    }
+    let ordering = match (a, b) {
+        (Value::Number(x), Value::Number(y)) => x
+            .partial_cmp(y)
+            .ok_or_else(|| Error::custom("Cannot compare NaN"))?,
+        (Value::String(x), Value::String(y)) => x.cmp(y),
+        _ => unreachable!("operand types are checked above"),
+    };
+    Ok(if ordering == pick.reverse() {
+        b.clone()
+    } else {
+        a.clone()
+    })
+}
+
+// `{{` and `}}` are literal braces.
+fn format(interpreter: &Interpreter, template: &str, args: &[Value]) -> Result<String> {
+    let mut formatted = String::new();
+    let mut placeholders = 0;
+    let mut chars = template.chars().peekable();
+    while let Some(ch) = chars.next() {
+        match (ch, chars.peek()) {
+            ('{', Some('{')) | ('}', Some('}')) => {
+                chars.next();
+                formatted.push(ch);
+            }
+            ('{', Some('}')) => {
+                chars.next();
+                if let Some(arg) = args.get(placeholders) {
+                    formatted.push_str(&interpreter.display(arg));
+                }
+                placeholders += 1;
+            }
+            ('{', _) | ('}', _) => {
+                return Err(Error::custom(format!(
+                    "Unmatched '{}' in format string",
+                    ch
+                )));
+            }
+            _ => formatted.push(ch),
+        }
+    }
+    if placeholders != args.len() {
+        return Err(Error::custom(format!(
+            "Format string has {} placeholders but got {} arguments",
+            placeholders,
+            args.len()
+        )));
+    }
+    Ok(formatted)
+}
+
+fn targets_loop(target: &Option<String>, label: Option<&Token>) -> bool {
+    match (target, label) {
+        (None, _) => true,
+        (Some(target), Some(label)) => *target == label.text,
+        (Some(_), None) => false,
+    }
+}
+
+type Env = Rc<RefCell<Environment>>;
+
+#[derive(Default, Debug)]
 struct Environment {
     enclosing: Option<Env>,
     store: HashMap<String, Value>,
+    // Only this scope's own, so nested scopes can shadow them.
+    constants: HashSet<String>,
 }
 
 impl Environment {
@@ -400,14 +1405,100 @@ impl Environment {
         Self {
             enclosing,
             store: HashMap::new(),
+            constants: HashSet::new(),
         }
     }
 
-    fn define(&mut self, name: &Token, value: Value) {
-        self.store.insert(name.text.clone(), value);
+    fn define_native(
+        &mut self,
+        name: &str,
+        arity: Arity,
+        function: impl Fn(&mut Interpreter, &[Value]) -> Result<Value> + 'static,
+    ) {
+        self.insert_native(name, arity, false, Rc::new(function));
+    }
+
+    fn define_skippable_native(
+        &mut self,
+        name: &str,
+        arity: Arity,
+        function: impl Fn(&mut Interpreter, &[Value]) -> Result<Value> + 'static,
+    ) {
+        self.insert_native(name, arity, true, Rc::new(function));
+    }
+
+    fn insert_native(&mut self, name: &str, arity: Arity, skippable: bool, function: NativeFn) {
+        self.store.insert(
+            name.to_string(),
+            Value::NativeFunction(NativeFunction {
+                arity,
+                name: name.to_string(),
+                function,
+                skippable,
+            }),
+        );
+    }
+
+    // Innermost scope first, with shadowed names listed once.
+    fn names(&self) -> Vec<String> {
+        let mut names: Vec<String> = self.store.keys().cloned().collect();
+        names.sort();
+        if let Some(ref enclosing) = self.enclosing {
+            for name in RefCell::borrow(enclosing).names() {
+                if !self.store.contains_key(&name) {
+                    names.push(name);
+                }
+            }
+        }
+        names
+    }
+
+    // Natives only live in the global scope, at the root of the chain.
+    fn native_names(&self) -> Vec<String> {
+        if let Some(ref enclosing) = self.enclosing {
+            return RefCell::borrow(enclosing).native_names();
+        }
+        let mut names: Vec<String> = self
+            .store
+            .iter()
+            .filter(|(_, value)| matches!(value, Value::NativeFunction(_)))
+            .map(|(name, _)| name.clone())
+            .collect();
+        names.sort();
+        names
+    }
+
+    fn define(&mut self, name: &str, value: Value) {
+        self.store.insert(name.to_string(), value);
+    }
+
+    // Redeclaring a constant in its own scope is refused like assigning to it.
+    fn declare(&mut self, name: &Token, value: Value) -> Result<()> {
+        if self.constants.contains(&name.text) {
+            return Err(Error::runtime(
+                RuntimeErrorKind::ConstantAssignment,
+                name,
+                &format!("Cannot redeclare constant {}", name.text),
+            ));
+        }
+        self.define(&name.text, value);
+        Ok(())
+    }
+
+    fn define_constant(&mut self, name: &Token, value: Value) -> Result<()> {
+        self.declare(name, value)?;
+        self.constants.insert(name.text.clone());
+        Ok(())
     }
 
     fn assign(&mut self, name: &Token, value: &Value) -> Result<Value> {
+        if self.constants.contains(&name.text) {
+            return Err(Error::runtime(
+                RuntimeErrorKind::ConstantAssignment,
+                name,
+                &format!("Cannot assign to constant {}", name.text),
+            ));
+        }
         if self.store.contains_key(&name.text) {
             self.store.insert(name.text.clone(), value.clone());
             return Ok(value.clone());
@@ -418,6 +1509,7 @@ impl Environment {
         }
 
         Err(Error::runtime(
+            RuntimeErrorKind::UndefinedVariable,
             name,
             format!("Undefined variable {}", name.text).as_str(),
         ))
@@ -431,8 +1523,946 @@ impl Environment {
             return RefCell::borrow(enclosing).get(name);
         }
         Err(Error::runtime(
+            RuntimeErrorKind::UndefinedVariable,
             name,
             format!("Undefined variable: {}", name.text).as_str(),
         ))
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{parser::Parser, scanner::Scanner};
+
+    fn run(interpreter: &mut Interpreter, source: &str) -> Result<()> {
+        let mut scanner = Scanner::new(source.to_string());
+        let stmts = Parser::new(scanner.scan_tokens()?).parse()?;
+        interpreter.interpret(stmts)
+    }
+
+    fn eval(interpreter: &mut Interpreter, source: &str) -> Value {
+        let expr = crate::parse_expr(source).unwrap();
+        interpreter.interpret_expression(&expr).unwrap()
+    }
+
+    // Runs `source` in a fresh interpreter and evaluates `expr` afterwards.
+    fn result_of(source: &str, expr: &str) -> Value {
+        let mut interpreter = Interpreter::new();
+        run(&mut interpreter, source).unwrap();
+        eval(&mut interpreter, expr)
+    }
+
+    fn string(value: &str) -> Value {
+        Value::String(value.to_string())
+    }
+
+    #[test]
+    fn globals_persist_across_interpret_calls() {
+        let mut interpreter = Interpreter::new();
+        run(
+            &mut interpreter,
+            "var x = 1; fun double(n) { return n * 2; }",
+        )
+        .unwrap();
+        run(&mut interpreter, "x = double(x) + 1;").unwrap();
+        run(&mut interpreter, "var y = double(x);").unwrap();
+        assert_eq!(eval(&mut interpreter, "x"), Value::Number(3.0));
+        assert_eq!(eval(&mut interpreter, "y"), Value::Number(6.0));
+
+        // A failed run leaves earlier globals in place and the interpreter usable.
+        assert!(run(&mut interpreter, "x = 10; nil();").is_err());
+        run(&mut interpreter, "{ var x = 0; }").unwrap();
+        assert_eq!(eval(&mut interpreter, "x"), Value::Number(10.0));
+    }
+
+    #[test]
+    fn for_clauses_can_each_be_left_out() {
+        let cases = [
+            ("for (var i = 0; i < 10; i = i + 2) log = log + \"${i}\";", "02468"),
+            ("var i = 0; for (; i < 3; i = i + 1) log = log + \"${i}\";", "012"),
+            (
+                "for (var i = 0; i < 5;) { i = i + 1; if (i == 2) continue; log = log + \"${i}\"; }",
+                "1345",
+            ),
+            ("for (var i = 0;; i = i + 1) { if (i == 3) break; log = log + \"${i}\"; }", "012"),
+            ("var i = 0; for (;;) { i = i + 1; if (i > 2) break; log = log + \"${i}\"; }", "12"),
+            ("for (var i = 0; i < 4; i = i + 1) { if (i == 1) continue; log = log + \"${i}\"; }", "023"),
+        ];
+        for (source, expected) in cases {
+            let source = format!("var log = \"\"; {source}");
+            assert_eq!(result_of(&source, "log"), string(expected), "{source}");
+        }
+    }
+
+    #[test]
+    fn else_if_chains_pick_one_arm_and_else_binds_to_the_inner_if() {
+        let chain = "var arm;
+            if (n == 1) arm = 1;
+            else if (n == 2) arm = 2;
+            else if (n == 3) arm = 3;
+            else if (n == 4) arm = 4;
+            else arm = 5;";
+        for n in 1..=5 {
+            let source = format!("var n = {n}; {chain}");
+            assert_eq!(result_of(&source, "arm"), Value::Number(n as f64));
+        }
+
+        let dangling = "var x = \"unset\"; if (a) if (b) x = \"then\"; else x = \"else\";";
+        let cases = [
+            ("true", "true", "then"),
+            ("true", "false", "else"),
+            ("false", "false", "unset"),
+        ];
+        for (a, b, expected) in cases {
+            let source = format!("var a = {a}; var b = {b}; {dangling}");
+            assert_eq!(result_of(&source, "x"), string(expected), "{a} {b}");
+        }
+    }
+
+    #[test]
+    fn while_conditions_see_changes_made_by_the_body() {
+        assert_eq!(
+            result_of("var i = 0; while (i < 3) { i = i + 1; }", "i"),
+            Value::Number(3.0)
+        );
+        let source = "var running = true; var runs = 0;
+            fun stop() { running = false; }
+            while (running) { runs = runs + 1; if (runs == 4) stop(); }";
+        assert_eq!(result_of(source, "runs"), Value::Number(4.0));
+        assert_eq!(
+            result_of("var runs = 0; while (false) { runs = runs + 1; }", "runs"),
+            Value::Number(0.0)
+        );
+    }
+
+    #[test]
+    fn assignment_chains_pass_the_value_along() {
+        let mut interpreter = Interpreter::new();
+        run(&mut interpreter, "var a; var b; a = b = 5;").unwrap();
+        assert_eq!(eval(&mut interpreter, "a"), Value::Number(5.0));
+        assert_eq!(eval(&mut interpreter, "b"), Value::Number(5.0));
+        assert_eq!(eval(&mut interpreter, "(a = 3)"), Value::Number(3.0));
+        assert_eq!(eval(&mut interpreter, "a"), Value::Number(3.0));
+    }
+
+    #[test]
+    fn for_loop_variables_are_scoped_to_the_loop() {
+        let err = run(
+            &mut Interpreter::new(),
+            "for (var i = 0; i < 3; i = i + 1) {} i;",
+        );
+        assert!(matches!(
+            err,
+            Err(Error::RuntimeError {
+                kind: RuntimeErrorKind::UndefinedVariable,
+                ..
+            })
+        ));
+
+        let source = "var i = \"outer\"; var sum = 0;
+            for (var i = 0; i < 5; i = i + 1) { if (i == 2) continue; sum = sum + i; }";
+        let mut interpreter = Interpreter::new();
+        run(&mut interpreter, source).unwrap();
+        assert_eq!(eval(&mut interpreter, "i"), string("outer"));
+        assert_eq!(eval(&mut interpreter, "sum"), Value::Number(8.0));
+    }
+
+    #[test]
+    fn break_in_a_case_leaves_only_the_switch() {
+        let source = r#"
+            var log = "";
+            for (var i = 0; i < 3; i = i + 1) {
+                switch (i) {
+                    case 0: log = log + "zero "; break; log = log + "unreached ";
+                    default: log = log + "other ";
+                }
+                log = log + "${i} ";
+            }
+        "#;
+        assert_eq!(result_of(source, "log"), string("zero 0 other 1 other 2 "));
+
+        let source = r#"
+            var log = "";
+            outer: for (var i = 0; i < 3; i = i + 1) {
+                switch (i) {
+                    case 1: break outer;
+                    default: log = log + "${i}";
+                }
+            }
+        "#;
+        assert_eq!(result_of(source, "log"), string("0"));
+        assert!(run(&mut Interpreter::new(), "switch (1) { case 1: break; }").is_ok());
+    }
+
+    #[test]
+    fn constants_cannot_be_redeclared_in_their_own_scope() {
+        for redeclaration in ["var x = 2;", "const x = 2;", "fun x() {}"] {
+            let mut interpreter = Interpreter::new();
+            let err = run(&mut interpreter, &format!("const x = 1; {redeclaration}"));
+            assert!(matches!(
+                err,
+                Err(Error::RuntimeError {
+                    kind: RuntimeErrorKind::ConstantAssignment,
+                    ..
+                })
+            ));
+            assert_eq!(eval(&mut interpreter, "x"), Value::Number(1.0));
+        }
+
+        let source = "const x = 1; var y; { var x = 2; x = 3; y = x; }";
+        assert_eq!(result_of(source, "y"), Value::Number(3.0));
+    }
+
+    #[test]
+    fn integer_conversions_reject_what_a_cast_would_mangle() {
+        for value in [f64::NAN, f64::INFINITY, 1.5, 1e30, -1e30] {
+            assert!(Value::Number(value).as_integer().is_err(), "{value}");
+        }
+        assert!(Value::String("1".to_string()).as_integer().is_err());
+        assert!(Value::Number(-1.0).as_index().is_err());
+        assert_eq!(Value::Number(-3.0).as_integer().unwrap(), -3);
+        assert_eq!(Value::Number(3.0).as_index().unwrap(), 3);
+
+        for source in [
+            "setPrecision(-1);",
+            "setPrecision(1000000000000 * 1000000000000);",
+            "round(1, 0.5);",
+            "parseInt(\"1\", 2.5);",
+            "parseInt(\"1\", 37);",
+            "slice(\"abc\", 0/0);",
+            "repeat (-1) {}",
+        ] {
+            let err = run(&mut Interpreter::new(), source).unwrap_err();
+            assert!(
+                matches!(err, Error::RuntimeError { line: 1, .. }),
+                "{source}: {err}"
+            );
+        }
+        assert_eq!(result_of("var s = slice(\"abcd\", -2);", "s"), string("cd"));
+    }
+
+    #[test]
+    fn only_check_is_skipped_when_checks_are_off() {
+        let mut interpreter = Interpreter::new();
+        interpreter.set_checks(false);
+        interpreter.define_native("check", Arity::exactly(1), |_interpreter, _args| {
+            Err(Error::custom("Host check failed"))
+        });
+        assert!(run(&mut interpreter, "check(false);").is_err());
+
+        let mut interpreter = Interpreter::new();
+        interpreter.set_checks(false);
+        let source = "var calls = 0; fun f() { calls = calls + 1; return false; } check(f());";
+        run(&mut interpreter, source).unwrap();
+        run(&mut interpreter, "var assert = check; assert(f());").unwrap();
+        assert_eq!(eval(&mut interpreter, "calls"), Value::Number(0.0));
+    }
+
+    #[test]
+    fn precision_rounds_displayed_numbers() {
+        let mut interpreter = Interpreter::new();
+        assert_eq!(
+            interpreter.display(&Value::Number(1.0 / 3.0)),
+            "0.3333333333333333"
+        );
+        interpreter.set_precision(NonZeroUsize::new(3));
+        assert_eq!(interpreter.display(&Value::Number(1.0 / 3.0)), "0.333");
+        run(&mut interpreter, "setPrecision(0);").unwrap();
+        assert_eq!(
+            interpreter.display(&Value::Number(2.0 / 3.0)),
+            "0.6666666666666666"
+        );
+    }
+
+    #[test]
+    fn equality_follows_lox_rules() {
+        let cases = [
+            ("0/0 == 0/0", false),
+            ("0 == -0", true),
+            ("1 == \"1\"", false),
+            ("nil == false", false),
+            ("nil == nil", true),
+            ("\"a\" + \"b\" == \"ab\"", true),
+            ("clock == clock", true),
+            ("clock == repr", false),
+        ];
+        let mut interpreter = Interpreter::new();
+        run(&mut interpreter, "fun f() {} fun g() {}").unwrap();
+        for (expr, expected) in cases {
+            assert_eq!(
+                eval(&mut interpreter, expr),
+                Value::Boolean(expected),
+                "{expr}"
+            );
+        }
+        assert_eq!(eval(&mut interpreter, "f == f"), Value::Boolean(true));
+        assert_eq!(eval(&mut interpreter, "f == g"), Value::Boolean(false));
+    }
+
+    #[test]
+    fn labeled_break_and_continue_target_their_loop() {
+        let source = r#"
+            var log = "";
+            outer: for (var i = 0; i < 3; i = i + 1) {
+                for (var j = 0; j < 3; j = j + 1) {
+                    if (j == 1) continue outer;
+                    if (i == 2) break outer;
+                    log = log + "${i}${j} ";
+                }
+            }
+        "#;
+        assert_eq!(result_of(source, "log"), string("00 10 "));
+
+        let source = r#"
+            var log = "";
+            outer: while (true) {
+                while (true) { log = log + "inner "; break; }
+                break outer;
+            }
+        "#;
+        assert_eq!(result_of(source, "log"), string("inner "));
+    }
+
+    #[test]
+    fn do_while_runs_its_body_at_least_once() {
+        assert_eq!(
+            result_of(
+                "var runs = 0; do { runs = runs + 1; } while (false);",
+                "runs"
+            ),
+            Value::Number(1.0)
+        );
+        let source = "var i = 0; var sum = 0;
+            do { i = i + 1; if (i == 2) continue; sum = sum + i; } while (i < 4);";
+        assert_eq!(result_of(source, "sum"), Value::Number(8.0));
+    }
+
+    #[test]
+    fn coalesce_only_replaces_nil() {
+        let mut interpreter = Interpreter::new();
+        assert_eq!(eval(&mut interpreter, "nil ?? 1"), Value::Number(1.0));
+        assert_eq!(eval(&mut interpreter, "false ?? 1"), Value::Boolean(false));
+        assert_eq!(eval(&mut interpreter, "0 ?? 1"), Value::Number(0.0));
+        assert_eq!(
+            eval(&mut interpreter, "nil ?? nil ?? 2"),
+            Value::Number(2.0)
+        );
+
+        // The right side is only evaluated when it's needed.
+        let source = "var calls = 0; fun f() { calls = calls + 1; return 2; } var x = 1 ?? f();";
+        assert_eq!(result_of(source, "calls"), Value::Number(0.0));
+    }
+
+    #[test]
+    fn numbers_display_in_their_shortest_form() {
+        let cases = [
+            (1.0, "1"),
+            (-0.0, "0"),
+            (0.1 + 0.2, "0.30000000000000004"),
+            (1e21, "1e+21"),
+            (1e20, "100000000000000000000"),
+            (1.5e-7, "1.5e-7"),
+            (0.000001, "0.000001"),
+            (-2.5e300, "-2.5e+300"),
+            (f64::NAN, "NaN"),
+            (f64::NEG_INFINITY, "-Infinity"),
+        ];
+        for (num, expected) in cases {
+            assert_eq!(Value::Number(num).to_string(), expected);
+        }
+    }
+
+    #[test]
+    fn logical_assignment_only_assigns_when_needed() {
+        let source = "var a = nil; var b = 1; var c = 1; var d = nil;
+            a or= 2; b or= 3; c and= 4; d and= 5;";
+        let mut interpreter = Interpreter::new();
+        run(&mut interpreter, source).unwrap();
+        assert_eq!(eval(&mut interpreter, "a"), Value::Number(2.0));
+        assert_eq!(eval(&mut interpreter, "b"), Value::Number(1.0));
+        assert_eq!(eval(&mut interpreter, "c"), Value::Number(4.0));
+        assert_eq!(eval(&mut interpreter, "d"), Value::Nil);
+
+        // The right side isn't evaluated when no assignment happens.
+        let source = "var calls = 0; fun f() { calls = calls + 1; } var x = true; x or= f();";
+        assert_eq!(result_of(source, "calls"), Value::Number(0.0));
+    }
+
+    #[test]
+    fn trailing_parameters_can_have_defaults() {
+        let mut interpreter = Interpreter::new();
+        run(
+            &mut interpreter,
+            "fun f(a, b = a * 2, c = b + 1) { return \"${a} ${b} ${c}\"; }",
+        )
+        .unwrap();
+        assert_eq!(eval(&mut interpreter, "f(1)"), string("1 2 3"));
+        assert_eq!(eval(&mut interpreter, "f(1, 5)"), string("1 5 6"));
+        assert_eq!(eval(&mut interpreter, "f(1, 5, 0)"), string("1 5 0"));
+        assert!(run(&mut interpreter, "f();").is_err());
+        assert!(run(&mut interpreter, "f(1, 2, 3, 4);").is_err());
+        assert!(run(&mut interpreter, "fun g(a = 1, b) {}").is_err());
+    }
+
+    #[test]
+    fn format_fills_placeholders_in_order() {
+        let mut interpreter = Interpreter::new();
+        assert_eq!(
+            eval(&mut interpreter, "format(\"{} + {} = {}\", 1, 2, 1 + 2)"),
+            string("1 + 2 = 3")
+        );
+        assert_eq!(
+            eval(&mut interpreter, "format(\"{{}} {}\", nil)"),
+            string("{} null")
+        );
+        for source in [
+            "format(\"{}\");",
+            "format(\"{}\", 1, 2);",
+            "format(\"{\", 1);",
+        ] {
+            assert!(run(&mut interpreter, source).is_err(), "{source}");
+        }
+    }
+
+    #[test]
+    fn a_trailing_expression_is_returned_implicitly() {
+        let mut interpreter = Interpreter::new();
+        let source = "fun add(a, b) { a + b; }
+            fun early(x) { if (x) return \"early\"; \"late\"; }
+            fun none() { var x = 1; }";
+        run(&mut interpreter, source).unwrap();
+        assert_eq!(eval(&mut interpreter, "add(1, 2)"), Value::Number(3.0));
+        assert_eq!(eval(&mut interpreter, "early(true)"), string("early"));
+        assert_eq!(eval(&mut interpreter, "early(false)"), string("late"));
+        assert_eq!(eval(&mut interpreter, "none()"), Value::Nil);
+    }
+
+    #[test]
+    fn repeat_runs_its_body_a_fixed_number_of_times() {
+        let source = "var n = 2; var runs = 0; repeat (n + 1) { runs = runs + 1; n = 10; }";
+        assert_eq!(result_of(source, "runs"), Value::Number(3.0));
+        assert_eq!(
+            result_of("var runs = 0; repeat (0) runs = runs + 1;", "runs"),
+            Value::Number(0.0)
+        );
+        let source = "var runs = 0; repeat (5) { runs = runs + 1; if (runs == 2) break; }";
+        assert_eq!(result_of(source, "runs"), Value::Number(2.0));
+        assert!(run(&mut Interpreter::new(), "repeat (1.5) {}").is_err());
+    }
+
+    #[test]
+    fn is_tests_a_value_against_a_type_name() {
+        let mut interpreter = Interpreter::new();
+        run(&mut interpreter, "fun f() {}").unwrap();
+        let cases = [
+            ("1 is number", true),
+            ("\"1\" is number", false),
+            ("\"1\" is string", true),
+            ("true is boolean", true),
+            ("nil is nil", true),
+            ("f is function", true),
+            ("clock is function", true),
+            ("1 + 2 is number", true),
+        ];
+        for (expr, expected) in cases {
+            assert_eq!(
+                eval(&mut interpreter, expr),
+                Value::Boolean(expected),
+                "{expr}"
+            );
+        }
+        assert!(run(&mut interpreter, "1 is integer;").is_err());
+    }
+
+    #[test]
+    fn interpolation_embeds_expressions_in_strings() {
+        let mut interpreter = Interpreter::new();
+        run(&mut interpreter, "var name = \"Lox\"; var n = 2;").unwrap();
+        let cases = [
+            (r#""hi ${name}!""#, "hi Lox!"),
+            (r#""${n} + ${n} = ${n + n}""#, "2 + 2 = 4"),
+            (r#""outer ${"inner ${n}"}""#, "outer inner 2"),
+            (r#""${ { var x = n * 3; x } }""#, "6"),
+            (r#""cost: \${n}""#, "cost: ${n}"),
+            (r#""${nil}""#, "null"),
+        ];
+        for (expr, expected) in cases {
+            assert_eq!(eval(&mut interpreter, expr), string(expected), "{expr}");
+        }
+    }
+
+    #[test]
+    fn one_var_statement_can_bind_several_names() {
+        let mut interpreter = Interpreter::new();
+        run(&mut interpreter, "var a = 1, b = a + 1, c;").unwrap();
+        assert_eq!(eval(&mut interpreter, "a"), Value::Number(1.0));
+        assert_eq!(eval(&mut interpreter, "b"), Value::Number(2.0));
+        assert_eq!(eval(&mut interpreter, "c"), Value::Nil);
+        // The bindings land in the enclosing scope, not a block of their own.
+        run(&mut interpreter, "{ var d = 4, e = 5; a = d + e; }").unwrap();
+        assert_eq!(eval(&mut interpreter, "a"), Value::Number(9.0));
+    }
+
+    #[test]
+    fn strict_mode_rejects_reads_before_assignment() {
+        let mut interpreter = Interpreter::new();
+        interpreter.set_strict(true);
+        let err = run(&mut interpreter, "var x; print x;").unwrap_err();
+        assert!(matches!(
+            err,
+            Error::RuntimeError {
+                kind: RuntimeErrorKind::UnassignedVariable,
+                ..
+            }
+        ));
+        run(&mut interpreter, "x = 1; var y = nil;").unwrap();
+        assert_eq!(eval(&mut interpreter, "x"), Value::Number(1.0));
+        assert_eq!(eval(&mut interpreter, "y"), Value::Nil);
+
+        assert_eq!(result_of("var z;", "z"), Value::Nil);
+    }
+
+    #[test]
+    fn try_catches_runtime_errors_only() {
+        let source = r#"
+            var caught = nil; var after = false;
+            try { var x = 1 + nil; after = true; } catch (err) { caught = err; }
+        "#;
+        let mut interpreter = Interpreter::new();
+        run(&mut interpreter, source).unwrap();
+        assert!(matches!(eval(&mut interpreter, "caught"), Value::String(_)));
+        assert_eq!(eval(&mut interpreter, "after"), Value::Boolean(false));
+
+        // Control flow passes straight through.
+        let source = r#"
+            fun f() { try { return "returned"; } catch (err) { return "caught"; } }
+            var log = "";
+            for (var i = 0; i < 3; i = i + 1) { try { if (i == 1) break; } catch (err) {} log = log + "${i}"; }
+            var result = f();
+        "#;
+        run(&mut interpreter, source).unwrap();
+        assert_eq!(eval(&mut interpreter, "result"), string("returned"));
+        assert_eq!(eval(&mut interpreter, "log"), string("0"));
+    }
+
+    #[test]
+    fn thrown_values_reach_catch_as_their_display_text() {
+        let source = r#"
+            fun fail(n) { throw "bad ${n}"; }
+            var caught;
+            try { fail(3); } catch (err) { caught = err; }
+        "#;
+        assert_eq!(result_of(source, "caught"), string("bad 3"));
+
+        let err = run(&mut Interpreter::new(), "throw 42;").unwrap_err();
+        assert!(matches!(
+            err,
+            Error::RuntimeError {
+                kind: RuntimeErrorKind::Thrown,
+                ref msg,
+                ..
+            } if msg == "42"
+        ));
+    }
+
+    #[test]
+    fn repr_shows_values_typed() {
+        let mut interpreter = Interpreter::new();
+        let cases = [
+            (r#"repr("1")"#, r#""1""#),
+            ("repr(1)", "1"),
+            ("repr(nil)", "nil"),
+            (r#"repr("nil")"#, r#""nil""#),
+            (r#"repr("a\n\"b\"\\")"#, r#""a\n\"b\"\\""#),
+            (r#"repr("\${x} $x")"#, r#""\${x} $x""#),
+        ];
+        for (expr, expected) in cases {
+            assert_eq!(eval(&mut interpreter, expr), string(expected), "{expr}");
+        }
+        let original = eval(&mut interpreter, r#""tab\there\0 \${x}""#);
+        assert_eq!(eval(&mut interpreter, &original.repr()), original);
+    }
+
+    #[test]
+    fn slice_counts_negative_bounds_from_the_end() {
+        let mut interpreter = Interpreter::new();
+        let cases = [
+            (r#"slice("hello", 1, 3)"#, "el"),
+            (r#"slice("hello", -3)"#, "llo"),
+            (r#"slice("hello", 1, -1)"#, "ell"),
+            (r#"slice("hello", 3, 1)"#, ""),
+            (r#"slice("hello", -10, 10)"#, "hello"),
+            (r#"slice("héllo", 1, 2)"#, "é"),
+        ];
+        for (expr, expected) in cases {
+            assert_eq!(eval(&mut interpreter, expr), string(expected), "{expr}");
+        }
+        assert!(run(&mut interpreter, "slice(12345, 1);").is_err());
+    }
+
+    #[test]
+    fn round_rounds_half_away_from_zero() {
+        let mut interpreter = Interpreter::new();
+        let cases = [
+            ("round(2.5)", 3.0),
+            ("round(-2.5)", -3.0),
+            ("round(2.4)", 2.0),
+            ("round(2.71828, 2)", 2.72),
+            ("round(1234.5678, 0)", 1235.0),
+        ];
+        for (expr, expected) in cases {
+            assert_eq!(
+                eval(&mut interpreter, expr),
+                Value::Number(expected),
+                "{expr}"
+            );
+        }
+        assert!(run(&mut interpreter, "round(\"1\");").is_err());
+    }
+
+    #[test]
+    fn min_and_max_take_any_number_of_arguments() {
+        let mut interpreter = Interpreter::new();
+        assert_eq!(eval(&mut interpreter, "min(3)"), Value::Number(3.0));
+        assert_eq!(eval(&mut interpreter, "min(3, 1, 2)"), Value::Number(1.0));
+        assert_eq!(
+            eval(&mut interpreter, "max(3, 1, 5, 2)"),
+            Value::Number(5.0)
+        );
+        assert_eq!(eval(&mut interpreter, r#"max("a", "c", "b")"#), string("c"));
+        assert!(run(&mut interpreter, "min();").is_err());
+        assert!(run(&mut interpreter, r#"max(1, "2");"#).is_err());
+    }
+
+    #[test]
+    fn parse_int_and_parse_float_give_nil_for_bad_input() {
+        let mut interpreter = Interpreter::new();
+        let cases = [
+            (r#"parseInt("42")"#, Value::Number(42.0)),
+            (r#"parseInt(" -7 ")"#, Value::Number(-7.0)),
+            (r#"parseInt("ff", 16)"#, Value::Number(255.0)),
+            (r#"parseInt("1.5")"#, Value::Nil),
+            (r#"parseInt("")"#, Value::Nil),
+            (r#"parseFloat("1.5")"#, Value::Number(1.5)),
+            (r#"parseFloat("abc")"#, Value::Nil),
+            (r#"parseInt("99999999999999999999")"#, Value::Number(1e20)),
+            (r#"parseInt("-z", 36)"#, Value::Number(-35.0)),
+            (r#"parseInt("12", 2)"#, Value::Nil),
+            (r#"parseInt("-")"#, Value::Nil),
+            (r#"parseInt("+-1")"#, Value::Nil),
+            (r#"parseFloat("nan")"#, Value::Nil),
+            (r#"parseFloat("inf")"#, Value::Nil),
+            (r#"parseFloat("+Infinity")"#, Value::Nil),
+        ];
+        for (expr, expected) in cases {
+            assert_eq!(eval(&mut interpreter, expr), expected, "{expr}");
+        }
+        assert!(run(&mut interpreter, "parseInt(42);").is_err());
+        assert!(run(&mut interpreter, r#"parseInt("1", 1);"#).is_err());
+        assert!(run(&mut interpreter, r#"parseInt("1", 37);"#).is_err());
+    }
+
+    #[test]
+    fn bytes_round_trip_through_utf8_and_base64() {
+        let mut interpreter = Interpreter::new();
+        assert_eq!(
+            eval(&mut interpreter, r#"toBytes("hé")"#),
+            Value::Bytes(vec![b'h', 0xc3, 0xa9])
+        );
+        assert_eq!(
+            eval(&mut interpreter, r#"base64Encode(toBytes("Lox"))"#),
+            string("TG94")
+        );
+        assert_eq!(
+            eval(&mut interpreter, r#"fromBytes(base64Decode("TG94"))"#),
+            string("Lox")
+        );
+        assert!(run(&mut interpreter, r#"base64Decode("not base64!");"#).is_err());
+        assert!(run(&mut interpreter, r#"fromBytes(base64Decode("/w=="));"#).is_err());
+    }
+
+    #[test]
+    fn vars_lists_names_innermost_scope_first() {
+        let source = "var b = 1; var a = 2; var seen; { var c = 3; var b = 4; seen = vars(); }";
+        let Value::String(seen) = result_of(source, "seen") else {
+            panic!("vars() should return a string");
+        };
+        assert!(seen.starts_with("b, c, a, "), "{seen}");
+        assert_eq!(seen.matches("b, ").count(), 1, "{seen}");
+    }
+
+    #[test]
+    fn the_trace_hook_sees_each_statement() {
+        let lines = Rc::new(RefCell::new(vec![]));
+        let mut interpreter = Interpreter::new();
+        let seen = Rc::clone(&lines);
+        interpreter.set_trace(Box::new(move |stmt| {
+            seen.borrow_mut().push(matches!(stmt, Stmt::Print { .. }));
+        }));
+        run(&mut interpreter, "var x = 1;\nif (x) print x;").unwrap();
+        assert_eq!(*lines.borrow(), [false, false, true]);
+    }
+
+    #[test]
+    fn natives_can_capture_host_state() {
+        let calls = Rc::new(RefCell::new(0));
+        let mut interpreter = Interpreter::new();
+        let counter = Rc::clone(&calls);
+        interpreter.define_native("tick", Arity::exactly(0), move |_interpreter, _args| {
+            *counter.borrow_mut() += 1;
+            Ok(Value::Number(*counter.borrow() as f64))
+        });
+        run(&mut interpreter, "tick(); tick(); var last = tick();").unwrap();
+        assert_eq!(*calls.borrow(), 3);
+        assert_eq!(eval(&mut interpreter, "last"), Value::Number(3.0));
+    }
+
+    #[test]
+    fn bare_interpreters_have_no_natives() {
+        let mut interpreter = Interpreter::bare();
+        let err = run(&mut interpreter, "clock();").unwrap_err();
+        assert!(matches!(
+            err,
+            Error::RuntimeError {
+                kind: RuntimeErrorKind::UndefinedVariable,
+                ..
+            }
+        ));
+        interpreter.define_native("answer", Arity::exactly(0), |_interpreter, _args| {
+            Ok(Value::Number(42.0))
+        });
+        assert_eq!(eval(&mut interpreter, "answer()"), Value::Number(42.0));
+    }
+
+    #[test]
+    fn identical_functions_are_distinct_values() {
+        let source = "fun a() { return 1; } fun b() { return 1; } var alias = a;
+            fun make() { fun inner() {} return inner; } var one = make(); var two = make();";
+        let mut interpreter = Interpreter::new();
+        run(&mut interpreter, source).unwrap();
+        assert_eq!(eval(&mut interpreter, "a == b"), Value::Boolean(false));
+        assert_eq!(eval(&mut interpreter, "a == alias"), Value::Boolean(true));
+        assert_eq!(eval(&mut interpreter, "one == two"), Value::Boolean(false));
+    }
+
+    #[test]
+    fn local_functions_can_call_each_other() {
+        let source = "var result;
+            {
+                fun isEven(n) { if (n == 0) return true; return isOdd(n - 1); }
+                fun isOdd(n) { if (n == 0) return false; return isEven(n - 1); }
+                result = isEven(10) and isOdd(7) and isEven(3) == false;
+            }";
+        assert_eq!(result_of(source, "result"), Value::Boolean(true));
+    }
+
+    #[test]
+    fn assigning_to_a_constant_fails() {
+        let mut interpreter = Interpreter::new();
+        let err = run(&mut interpreter, "const x = 1;\nx = 2;").unwrap_err();
+        assert!(matches!(
+            err,
+            Error::RuntimeError {
+                kind: RuntimeErrorKind::ConstantAssignment,
+                line: 2,
+                ..
+            }
+        ));
+        assert_eq!(eval(&mut interpreter, "x"), Value::Number(1.0));
+        assert!(run(&mut interpreter, "{ x = 3; }").is_err());
+        assert!(run(&mut Interpreter::new(), "const y;").is_err());
+    }
+
+    #[test]
+    fn files_round_trip_through_write_file_and_read_file() {
+        let path = std::env::temp_dir().join(format!("jlox-io-{}.txt", std::process::id()));
+        let path = path.to_str().unwrap().replace('\\', "/");
+        let source = format!(
+            r#"write_file("{path}", "line one\nline two"); var text = read_file("{path}");"#
+        );
+        let text = result_of(&source, "text");
+        std::fs::remove_file(&path).unwrap();
+        assert_eq!(text, string("line one\nline two"));
+
+        let err = run(&mut Interpreter::new(), r#"read_file("/no/such/file");"#).unwrap_err();
+        assert!(matches!(
+            err,
+            Error::RuntimeError {
+                kind: RuntimeErrorKind::Native,
+                ref msg,
+                ..
+            } if msg.starts_with("Could not read '/no/such/file'")
+        ));
+        assert!(run(&mut Interpreter::new(), "read_file(1);").is_err());
+    }
+
+    #[test]
+    fn switch_runs_the_matching_case_or_default() {
+        let switch = r#"var log = "";
+            switch (x) {
+                case 1: log = "one";
+                case "two": log = "two";
+                default: log = "default";
+            }"#;
+        let with_default = [
+            ("1", "one"),
+            ("\"two\"", "two"),
+            ("2", "default"),
+            ("nil", "default"),
+        ];
+        for (x, expected) in with_default {
+            assert_eq!(
+                result_of(&format!("var x = {x}; {switch}"), "log"),
+                string(expected)
+            );
+        }
+
+        let source = r#"var x = 3; var log = "none"; switch (x) { case 1: log = "one"; case 2: log = "two"; }"#;
+        assert_eq!(result_of(source, "log"), string("none"));
+    }
+
+    #[test]
+    fn block_expressions_yield_their_trailing_expression() {
+        let mut interpreter = Interpreter::new();
+        run(
+            &mut interpreter,
+            "var t = 10; var x = { var t = 1; t + 1 }; var y = { var u = 1; };",
+        )
+        .unwrap();
+        assert_eq!(eval(&mut interpreter, "x"), Value::Number(2.0));
+        assert_eq!(eval(&mut interpreter, "y"), Value::Nil);
+        assert_eq!(eval(&mut interpreter, "{}"), Value::Nil);
+        assert_eq!(eval(&mut interpreter, "t"), Value::Number(10.0));
+    }
+
+    #[test]
+    fn leaving_nested_blocks_early_restores_the_environment() {
+        let mut interpreter = Interpreter::new();
+        let globals = Rc::clone(&interpreter.env);
+        let source = "fun f() { var a = 1; { var b = 2; { var c = 3; { return a + b + c; } } } }
+            var r = f();
+            while (true) { { var inner = 1; { break; } } }";
+        run(&mut interpreter, source).unwrap();
+        assert!(Rc::ptr_eq(&interpreter.env, &globals));
+        assert_eq!(eval(&mut interpreter, "r"), Value::Number(6.0));
+
+        assert!(run(&mut interpreter, "{ { var d = 4; nil(); } }").is_err());
+        assert!(Rc::ptr_eq(&interpreter.env, &globals));
+        assert!(run(&mut interpreter, "d;").is_err());
+    }
+
+    #[test]
+    fn negative_zero_prints_as_zero_but_keeps_its_sign() {
+        let mut interpreter = Interpreter::new();
+        assert_eq!(eval(&mut interpreter, "-0").to_string(), "0");
+        assert_eq!(eval(&mut interpreter, "0 * -1").to_string(), "0");
+        assert_eq!(eval(&mut interpreter, "1 / -0").to_string(), "-Infinity");
+        assert_eq!(eval(&mut interpreter, "1 / 0").to_string(), "Infinity");
+    }
+
+    #[test]
+    fn type_predicates() {
+        let mut interpreter = Interpreter::new();
+        run(&mut interpreter, "fun f() {}").unwrap();
+        let function = eval(&mut interpreter, "f");
+        let native = eval(&mut interpreter, "clock");
+        let others = [Value::Boolean(true), Value::Nil, Value::Bytes(vec![1])];
+
+        assert!(Value::Number(1.0).is_number());
+        assert!(Value::Number(f64::NAN).is_number());
+        assert!(!string("1").is_number());
+
+        assert!(string("").is_string());
+        assert!(!Value::Number(0.0).is_string());
+
+        assert!(function.is_callable());
+        assert!(native.is_callable());
+        assert!(!string("f").is_callable());
+
+        for value in others {
+            assert!(!value.is_number() && !value.is_string() && !value.is_callable());
+        }
+    }
+
+    #[test]
+    fn builtins_lists_only_native_functions() {
+        let mut interpreter = Interpreter::new();
+        run(&mut interpreter, "var clocks = 1; fun mine() {}").unwrap();
+        let Value::String(names) = eval(&mut interpreter, "builtins()") else {
+            panic!("builtins() should return a string");
+        };
+        let names: Vec<&str> = names.split(", ").collect();
+        for native in ["builtins", "clock", "contains", "inRange", "round", "slice"] {
+            assert!(names.contains(&native), "{native} missing from {names:?}");
+        }
+        assert!(!names.contains(&"clocks") && !names.contains(&"mine"));
+        assert!(names.windows(2).all(|pair| pair[0] < pair[1]));
+
+        interpreter.define_native("answer", Arity::exactly(0), |_interpreter, _args| {
+            Ok(Value::Number(42.0))
+        });
+        let Value::String(names) = eval(&mut interpreter, "builtins()") else {
+            panic!("builtins() should return a string");
+        };
+        assert!(names.split(", ").any(|name| name == "answer"));
+    }
+
+    #[test]
+    fn in_range_is_inclusive_and_needs_numbers() {
+        let mut interpreter = Interpreter::new();
+        let cases = [
+            ("inRange(5, 1, 10)", true),
+            ("inRange(1, 1, 10)", true),
+            ("inRange(10, 1, 10)", true),
+            ("inRange(0, 1, 10)", false),
+            ("inRange(10.5, 1, 10)", false),
+            ("inRange(5, 10, 1)", false),
+        ];
+        for (source, expected) in cases {
+            assert_eq!(
+                eval(&mut interpreter, source),
+                Value::Boolean(expected),
+                "{source}"
+            );
+        }
+
+        let err = run(&mut interpreter, "inRange(1, \"a\", 2);").unwrap_err();
+        assert!(
+            err.to_string()
+                .contains("inRange expects three numbers, got 1, \"a\" and 2"),
+            "{err}"
+        );
+    }
+
+    #[test]
+    fn contains_searches_strings() {
+        let mut interpreter = Interpreter::new();
+        let cases = [
+            ("contains(\"hello\", \"ell\")", true),
+            ("contains(\"hello\", \"\")", true),
+            ("contains(\"hello\", \"Hell\")", false),
+            ("contains(\"\", \"a\")", false),
+        ];
+        for (source, expected) in cases {
+            assert_eq!(
+                eval(&mut interpreter, source),
+                Value::Boolean(expected),
+                "{source}"
+            );
+        }
+
+        let err = run(&mut interpreter, "contains(1, 2);").unwrap_err();
+        assert!(
+            err.to_string()
+                .contains("Can only check containment in strings, got 1"),
+            "{err}"
+        );
+        let err = run(&mut interpreter, "contains(\"1\", 1);").unwrap_err();
+        assert!(
+            err.to_string()
+                .contains("Can only search a string for a string, got 1"),
+            "{err}"
+        );
+    }
+}
@@ -3,6 +3,7 @@ use interpreter::Value;
 use token::Token;
 
 pub mod expr;
+pub mod format;
 pub mod interpreter;
 pub mod parser;
 // pub mod print;
@@ -12,12 +13,28 @@ pub mod token;
 
 pub type Result<T> = core::result::Result<T, Error>;
 
+// Scans and parses source that must consist of exactly one expression.
+pub fn parse_expr(source: &str) -> Result<expr::Expr> {
+    let mut scanner = scanner::Scanner::new(source.to_string());
+    let tokens = scanner.scan_tokens()?;
+    parser::Parser::new(tokens).parse_single_expression()
+}
+
 #[derive(Debug, From)]
 pub enum Error {
     #[from]
     Custom(String),
 
+    Multiple(Vec<Error>),
+
+    ScanError {
+        file: Option<String>,
+        line: usize,
+        msg: String,
+    },
+
     ParseError {
+        file: Option<String>,
         line: usize,
         msg: String,
     },
@@ -26,7 +43,9 @@ pub enum Error {
     IO(std::io::Error),
 
     RuntimeError {
+        file: Option<String>,
         line: usize,
+        kind: RuntimeErrorKind,
         msg: String,
     },
 
@@ -34,20 +53,81 @@ pub enum Error {
         line: usize,
         value: Value,
     },
+
+    Break {
+        line: usize,
+        label: Option<String>,
+    },
+
+    Continue {
+        line: usize,
+        label: Option<String>,
+    },
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum RuntimeErrorKind {
+    UndefinedVariable,
+    // Read in strict mode before its first assignment.
+    UnassignedVariable,
+    TypeMismatch,
+    NotCallable,
+    Arity,
+    // Right type, wrong value, like a fractional count.
+    InvalidValue,
+    // Only reachable from hand-built syntax trees.
+    UnknownOperator,
+    ConstantAssignment,
+    Native,
+    Thrown,
 }
 
 impl Error {
-    pub fn runtime(token: &Token, message: &str) -> Error {
+    pub fn runtime(kind: RuntimeErrorKind, token: &Token, message: &str) -> Error {
         Error::RuntimeError {
+            file: None,
             line: token.line,
+            kind,
             msg: message.to_string(),
         }
     }
+
+    pub fn in_file(mut self, name: &str) -> Error {
+        match &mut self {
+            Error::ScanError { file, .. }
+            | Error::ParseError { file, .. }
+            | Error::RuntimeError { file, .. } => {
+                file.get_or_insert_with(|| name.to_string());
+            }
+            Error::Multiple(errors) => {
+                *errors = std::mem::take(errors)
+                    .into_iter()
+                    .map(|err| err.in_file(name))
+                    .collect();
+            }
+            _ => {}
+        }
+        self
+    }
 }
 
 impl core::fmt::Display for Error {
     fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
-        write!(f, "{self:?}")
+        match self {
+            Error::ScanError { file, line, msg }
+            | Error::ParseError { file, line, msg }
+            | Error::RuntimeError {
+                file, line, msg, ..
+            } => match file {
+                Some(file) => write!(f, "{file}:{line}: {msg}"),
+                None => write!(f, "[line {line}] {msg}"),
+            },
+            Error::Multiple(errors) => {
+                let errors: Vec<String> = errors.iter().map(|err| err.to_string()).collect();
+                write!(f, "{}", errors.join("\n"))
+            }
+            _ => write!(f, "{self:?}"),
+        }
     }
 }
 
@@ -57,6 +137,14 @@ impl Error {
     pub fn custom(value: impl std::fmt::Display) -> Self {
         Self::Custom(value.to_string())
     }
+
+    // Turns `Custom` into a runtime error on the line of `token`.
+    pub fn at(self, kind: RuntimeErrorKind, token: &Token) -> Error {
+        match self {
+            Error::Custom(msg) => Error::runtime(kind, token, &msg),
+            err => err,
+        }
+    }
 }
 
 impl From<&str> for Error {
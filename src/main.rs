@@ -1,46 +1,305 @@
-use jlox::interpreter::Interpreter;
-use jlox::{parser::Parser, scanner::Scanner, Result};
+use jlox::format::Formatter;
+use jlox::interpreter::{Interpreter, Value};
+use jlox::{parser::Parser, scanner::Scanner, stmt::Stmt, token::TokenType, Error, Result};
 use std::{
     env::args,
     fs,
-    io::{self, BufRead, Write},
+    io::{self, BufRead, IsTerminal, Write},
+    process,
 };
 
+struct Options {
+    prompt: String,
+    color: bool,
+    check: bool,
+    format: bool,
+    strict: bool,
+    debug_values: bool,
+    trace_calls: bool,
+    no_stdlib: bool,
+    no_checks: bool,
+}
+
 fn main() -> jlox::Result<()> {
-    let args: Vec<String> = args().collect();
-    match args.len().cmp(&2) {
-        std::cmp::Ordering::Greater => Err("Too many arguments")?,
-        std::cmp::Ordering::Equal => run_file(&args[1]),
-        std::cmp::Ordering::Less => run_prompt(),
+    let mut options = Options {
+        prompt: "> ".to_string(),
+        color: io::stderr().is_terminal(),
+        check: false,
+        format: false,
+        strict: false,
+        debug_values: false,
+        trace_calls: false,
+        no_stdlib: false,
+        no_checks: false,
+    };
+    let mut paths = vec![];
+    let mut eval = None;
+    let mut args = args().skip(1);
+    while let Some(arg) = args.next() {
+        if arg == "--check" {
+            options.check = true;
+        } else if arg == "--format" {
+            options.format = true;
+        } else if arg == "--strict" {
+            options.strict = true;
+        } else if arg == "--debug-values" {
+            options.debug_values = true;
+        } else if arg == "--trace-calls" {
+            options.trace_calls = true;
+        } else if arg == "--no-stdlib" {
+            options.no_stdlib = true;
+        } else if arg == "--no-checks" {
+            options.no_checks = true;
+        } else if arg == "--eval" {
+            eval = Some(args.next().ok_or("Missing expression after --eval")?);
+        } else if let Some(prompt) = arg.strip_prefix("--prompt=") {
+            options.prompt = prompt.to_string();
+        } else if let Some(color) = arg.strip_prefix("--color=") {
+            options.color = match color {
+                "always" => true,
+                "never" => false,
+                "auto" => io::stderr().is_terminal(),
+                _ => Err(format!("Invalid --color value: {color}"))?,
+            };
+        } else {
+            paths.push(arg);
+        }
     }
+
+    if options.check && paths.len() != 1 {
+        Err("--check needs exactly one script path")?
+    }
+    if options.format && paths.len() != 1 {
+        Err("--format needs exactly one script path")?
+    }
+    if let Some(source) = eval {
+        if !paths.is_empty() {
+            Err("Cannot combine --eval with a script path")?
+        }
+        return run_eval(source, &options);
+    }
+    match paths.as_slice() {
+        [] => run_prompt(&options),
+        [path] => run_file(path, &options),
+        _ => Err("Too many arguments")?,
+    }
+}
+
+fn new_interpreter(options: &Options) -> Interpreter {
+    let mut interpreter = if options.no_stdlib {
+        Interpreter::bare()
+    } else {
+        Interpreter::new()
+    };
+    interpreter.set_checks(!options.no_checks);
+    interpreter.set_strict(options.strict);
+    interpreter.set_debug_values(options.debug_values);
+    interpreter.set_trace_calls(options.trace_calls);
+    interpreter
 }
 
-fn run_prompt() -> Result<()> {
+fn run_prompt(options: &Options) -> Result<()> {
     let stdin = io::stdin();
-    print!("> ");
+    print!("{}", options.prompt);
     io::stdout().flush()?;
-    let mut interpreter = Interpreter::default();
+    let mut interpreter = new_interpreter(options);
+    let mut scanner = Scanner::default();
     for line in stdin.lock().lines() {
-        print!("> ");
+        print!("{}", options.prompt);
         io::stdout().flush()?;
-        if let Err(err) = run(line?, &mut interpreter) {
-            eprintln!("{err:?}")
+        // No source context: every REPL line starts again at line 1.
+        match run_line(&mut scanner, &mut interpreter, line?) {
+            Ok(Some(echo)) => println!("{echo}"),
+            Ok(None) => {}
+            Err(err) => report(&err, options),
+        }
+    }
+    Ok(())
+}
+
+// Runs one REPL line and returns what to echo back, if anything.
+fn run_line(
+    scanner: &mut Scanner,
+    interpreter: &mut Interpreter,
+    line: String,
+) -> Result<Option<String>> {
+    match line.trim_start().strip_prefix(":type") {
+        Some(expr) if expr.is_empty() || expr.starts_with(char::is_whitespace) => {
+            let value = eval(scanner, expr.to_string(), interpreter, "<repl>")?;
+            Ok(Some(value.type_name().to_string()))
+        }
+        _ if is_expression(scanner, &line) => {
+            let value = eval(scanner, line, interpreter, "<repl>")?;
+            let echo = interpreter.display(&value);
+            interpreter.define("_", value);
+            Ok(Some(echo))
         }
+        _ => run(scanner, line, interpreter, "<repl>", false).map(|_| None),
+    }
+}
+
+fn run_file(path: &str, options: &Options) -> Result<()> {
+    let source = String::from_utf8(fs::read(path)?)?;
+    let mut interpreter = new_interpreter(options);
+    let mut scanner = Scanner::default();
+    let result = if options.check {
+        parse(&mut scanner, source, path, true).map(|_| ())
+    } else if options.format {
+        parse(&mut scanner, source, path, false)
+            .map(|stmts| print!("{}", Formatter::format(&stmts)))
+    } else {
+        run(&mut scanner, source, &mut interpreter, path, true)
+    };
+    if let Err(err) = result {
+        report(&err, options);
+        report_context(&err, &interpreter);
+        process::exit(exit_code(&err));
     }
     Ok(())
 }
 
-fn run_file(path: &str) -> Result<()> {
-    let source = fs::read(path)?;
-    let mut interpreter = Interpreter::default();
-    run(String::from_utf8(source)?, &mut interpreter)
+fn run_eval(source: String, options: &Options) -> Result<()> {
+    let mut interpreter = new_interpreter(options);
+    match eval(&mut Scanner::default(), source, &mut interpreter, "<eval>") {
+        Ok(value) => println!("{}", interpreter.display(&value)),
+        Err(err) => {
+            report(&err, options);
+            report_context(&err, &interpreter);
+            process::exit(exit_code(&err));
+        }
+    }
+    Ok(())
+}
+
+fn eval(
+    scanner: &mut Scanner,
+    source: String,
+    interpreter: &mut Interpreter,
+    file: &str,
+) -> Result<Value> {
+    interpreter.set_source(&source);
+    scanner.reset(source);
+    let tokens = scanner.scan_tokens().map_err(|err| err.in_file(file))?;
+    let mut parser = Parser::new(tokens);
+    let expr = parser
+        .parse_single_expression()
+        .map_err(|err| err.in_file(file))?;
+    interpreter
+        .interpret_expression(&expr)
+        .map_err(|err| err.in_file(file))
+}
+
+// A leading `{` is a block statement, as in a script.
+fn is_expression(scanner: &mut Scanner, source: &str) -> bool {
+    scanner.reset(source.to_string());
+    scanner.scan_tokens().is_ok_and(|tokens| {
+        !matches!(tokens[0].token_type, TokenType::LeftBrace)
+            && Parser::new(tokens).parse_single_expression().is_ok()
+    })
 }
 
-fn run(source: String, interpreter: &mut Interpreter) -> Result<()> {
-    let mut scanner = Scanner::new(source);
-    let tokens = scanner.scan_tokens();
+fn parse(scanner: &mut Scanner, source: String, file: &str, warn: bool) -> Result<Vec<Stmt>> {
+    scanner.reset(source);
+    let tokens = scanner.scan_tokens().map_err(|err| err.in_file(file))?;
     let mut parser = Parser::new(tokens);
-    let stmts = parser.parse()?;
-    interpreter.interpret(stmts)?;
+    let stmts = parser.parse().map_err(|err| err.in_file(file))?;
+    if warn {
+        for warning in parser.warnings() {
+            eprintln!("{file}:{}: warning: {}", warning.line, warning.msg);
+        }
+    }
+    Ok(stmts)
+}
+
+fn run(
+    scanner: &mut Scanner,
+    source: String,
+    interpreter: &mut Interpreter,
+    file: &str,
+    warn: bool,
+) -> Result<()> {
+    interpreter.set_source(&source);
+    let stmts = parse(scanner, source, file, warn)?;
+    interpreter
+        .interpret(stmts)
+        .map_err(|err| err.in_file(file))?;
     Ok(())
 }
+
+fn report(err: &Error, options: &Options) {
+    if options.color {
+        eprintln!("\x1b[31m{err}\x1b[0m");
+    } else {
+        eprintln!("{err}");
+    }
+}
+
+fn report_context(err: &Error, interpreter: &Interpreter) {
+    if let Error::RuntimeError { line, .. } = err {
+        if let Some(source) = interpreter.source_line(*line) {
+            eprintln!("{line:>5} | {source}");
+        }
+    }
+}
+
+// As in sysexits.h.
+fn exit_code(err: &Error) -> i32 {
+    match err {
+        Error::ScanError { .. } | Error::ParseError { .. } => 65,
+        Error::Multiple(errors) => errors.first().map_or(65, exit_code),
+        Error::RuntimeError { .. } => 70,
+        _ => 1,
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn repl_echoes_only_bare_expressions() {
+        let mut scanner = Scanner::default();
+        assert!(is_expression(&mut scanner, "2 + 2"));
+        assert!(is_expression(&mut scanner, "_ * 10"));
+        assert!(!is_expression(&mut scanner, "print 1;"));
+        assert!(!is_expression(&mut scanner, "var x = 1;"));
+        assert!(!is_expression(&mut scanner, "{ print 1; }"));
+        assert!(!is_expression(&mut scanner, "{ var q = 2; }"));
+        assert!(!is_expression(&mut scanner, ""));
+    }
+
+    fn repl(lines: &[&str]) -> Vec<Option<String>> {
+        let mut scanner = Scanner::default();
+        let mut interpreter = Interpreter::new();
+        lines
+            .iter()
+            .map(|line| run_line(&mut scanner, &mut interpreter, line.to_string()).ok()?)
+            .collect()
+    }
+
+    #[test]
+    fn repl_keeps_the_last_value_in_underscore() {
+        let echoed = repl(&["1 + 1", "_ * 10", "print _;", "_", "nil + 1", "_"]);
+        let expected = [Some("2"), Some("20"), None, Some("20"), None, Some("20")];
+        assert_eq!(echoed, expected.map(|echo| echo.map(str::to_string)));
+    }
+
+    #[test]
+    fn repl_type_shows_the_type_name() {
+        let echoed = repl(&[
+            ":type 1",
+            ":type \"a\" + \"b\"",
+            ":type clock",
+            "var x;",
+            ":type x",
+        ]);
+        let expected = [
+            Some("number"),
+            Some("string"),
+            Some("function"),
+            None,
+            Some("nil"),
+        ];
+        assert_eq!(echoed, expected.map(|echo| echo.map(str::to_string)));
+    }
+}
@@ -1,11 +1,11 @@
-use crate::expr::Expr;
-use crate::stmt::{FunctionDeclaration, Stmt};
+use crate::expr::{Expr, Literal};
+use crate::stmt::{FunctionDeclaration, Parameter, Stmt, SwitchCase};
 use crate::token::{Token, TokenType};
 use crate::{Error, Result};
 
 macro_rules! match_next {
     ($self: ident, $p:pat) => {
-        if let $p = $self.peek().token_type {
+        if let $p = $self.peek_type() {
             $self.advance();
             true
         } else {
@@ -16,7 +16,7 @@ macro_rules! match_next {
 
 macro_rules! consume_next {
     ($self: ident, $p:pat, $e: expr) => {
-        match $self.peek().token_type {
+        match $self.peek_type() {
             $p => $self.advance(),
             _ => {
                 return Err($self.error($e));
@@ -25,69 +25,232 @@ macro_rules! consume_next {
     };
 }
 
+// Deeper nesting would overflow the stack, so it is a parse error.
+const DEFAULT_MAX_DEPTH: usize = 128;
+
 pub struct Parser<'a> {
     tokens: &'a Vec<Token>,
     current: usize,
+    depth: usize,
+    max_depth: usize,
+    too_deep: bool,
+    // Enclosing loops' labels, innermost last; `None` for unlabeled loops.
+    loops: Vec<Option<String>>,
+    switches: usize,
+    errors: Vec<Error>,
+    warnings: Vec<Warning>,
+}
+
+// Something that parses but is probably a mistake.
+#[derive(Debug, Clone, PartialEq)]
+pub struct Warning {
+    pub line: usize,
+    pub msg: String,
 }
 
 impl<'a> Parser<'a> {
     pub fn new(tokens: &'a Vec<Token>) -> Self {
-        Self { tokens, current: 0 }
+        Self {
+            tokens,
+            current: 0,
+            depth: 0,
+            max_depth: DEFAULT_MAX_DEPTH,
+            too_deep: false,
+            loops: vec![],
+            switches: 0,
+            errors: vec![],
+            warnings: vec![],
+        }
+    }
+
+    pub fn with_max_depth(mut self, max_depth: usize) -> Self {
+        self.max_depth = max_depth;
+        self
     }
 
     pub fn parse(&mut self) -> Result<Vec<Stmt>> {
         let mut statements = vec![];
         while !self.at_end() {
-            statements.push(self.parse_declaration_statement()?);
+            statements.extend(self.parse_declaration_recovering());
         }
+        self.finish(statements)
+    }
 
-        Ok(statements)
+    // The whole input as one expression, rejecting trailing tokens.
+    pub fn parse_single_expression(&mut self) -> Result<Expr> {
+        let expr = self.parse_expression()?;
+        if !self.at_end() {
+            return Err(self.error("Expect end of input after expression"));
+        }
+        self.finish(expr)
+    }
+
+    pub fn warnings(&self) -> &[Warning] {
+        &self.warnings
+    }
+
+    fn finish<T>(&mut self, parsed: T) -> Result<T> {
+        match self.errors.len() {
+            0 => Ok(parsed),
+            1 => Err(self.errors.remove(0)),
+            _ => Err(Error::Multiple(std::mem::take(&mut self.errors))),
+        }
+    }
+
+    // Records the error and skips to the next statement.
+    fn parse_declaration_recovering(&mut self) -> Option<Stmt> {
+        match self.parse_declaration_statement() {
+            Ok(stmt) => Some(stmt),
+            // Report the depth limit once rather than at every remaining level.
+            Err(_) if self.too_deep && self.current == self.tokens.len() - 1 => None,
+            Err(err) if self.too_deep => {
+                self.errors.push(err);
+                self.current = self.tokens.len() - 1;
+                None
+            }
+            Err(err) => {
+                self.errors.push(err);
+                self.synchronize();
+                None
+            }
+        }
+    }
+
+    fn synchronize(&mut self) {
+        self.advance();
+        while !self.at_end() {
+            if matches!(self.previous().token_type, TokenType::Semicolon)
+                || self.at_statement_start()
+            {
+                return;
+            }
+            self.advance();
+        }
     }
 
     fn parse_declaration_statement(&mut self) -> Result<Stmt> {
         if match_next!(self, TokenType::Fun) {
             return self.parse_function_declaration("function");
         }
-        if match_next!(self, TokenType::Return) {
-            return self.parse_return();
-        }
         if match_next!(self, TokenType::Var) {
             return self.parse_variable_declaration();
         }
+        if match_next!(self, TokenType::Const) {
+            return self.parse_const_declaration();
+        }
         self.parse_statement()
     }
 
     fn parse_statement(&mut self) -> Result<Stmt> {
+        if matches!(self.peek_type(), TokenType::Identifier)
+            && matches!(self.peek_next().token_type, TokenType::Colon)
+        {
+            return self.parse_labeled_loop();
+        }
         if match_next!(self, TokenType::For) {
-            return self.parse_for_statement();
+            return self.parse_for_statement(None);
         }
         if match_next!(self, TokenType::If) {
             return self.parse_if_statement();
         }
+        if match_next!(self, TokenType::Switch) {
+            return self.parse_switch_statement();
+        }
         if match_next!(self, TokenType::While) {
-            return self.parse_while_loop();
+            return self.parse_while_loop(None);
+        }
+        if match_next!(self, TokenType::Do) {
+            return self.parse_do_while_loop(None);
+        }
+        if match_next!(self, TokenType::Repeat) {
+            return self.parse_repeat_loop(None);
+        }
+        if match_next!(self, TokenType::Return) {
+            return self.parse_return();
+        }
+        if match_next!(self, TokenType::Break) {
+            return self.parse_break();
+        }
+        if match_next!(self, TokenType::Continue) {
+            return self.parse_continue();
+        }
+        if match_next!(self, TokenType::Throw) {
+            return self.parse_throw();
+        }
+        if match_next!(self, TokenType::Try) {
+            return self.parse_try_statement();
         }
         if match_next!(self, TokenType::Print) {
             return self.parse_print_statement();
         }
         if match_next!(self, TokenType::LeftBrace) {
-            return self.parse_block();
+            return self.nested(Self::parse_block);
         }
         self.parse_expression_statement()
     }
 
     fn parse_block(&mut self) -> Result<Stmt> {
+        let stmts = self.parse_block_stmts()?;
+        Ok(Stmt::Block { stmts })
+    }
+
+    fn parse_block_stmts(&mut self) -> Result<Vec<Stmt>> {
         let mut stmts = vec![];
-        while !matches!(self.peek().token_type, TokenType::RightBrace) && !self.at_end() {
-            stmts.push(self.parse_declaration_statement()?);
+        while !matches!(self.peek_type(), TokenType::RightBrace) && !self.at_end() {
+            stmts.extend(self.parse_declaration_recovering());
         }
         consume_next!(self, TokenType::RightBrace, "Expect } after block");
-        Ok(Stmt::Block { stmts })
+        Ok(stmts)
+    }
+
+    fn parse_try_statement(&mut self) -> Result<Stmt> {
+        consume_next!(self, TokenType::LeftBrace, "Expect '{' after 'try'.");
+        let body = self.nested(Self::parse_block_stmts)?;
+        consume_next!(self, TokenType::Catch, "Expect 'catch' after try block.");
+        consume_next!(self, TokenType::LeftParen, "Expect '(' after 'catch'.");
+        let error_name = consume_next!(self, TokenType::Identifier, "Expect error variable name.");
+        consume_next!(
+            self,
+            TokenType::RightParen,
+            "Expect ')' after error variable."
+        );
+        consume_next!(self, TokenType::LeftBrace, "Expect '{' before catch block.");
+        let handler = self.nested(Self::parse_block_stmts)?;
+        Ok(Stmt::Try {
+            body,
+            error_name,
+            handler,
+        })
+    }
+
+    fn parse_labeled_loop(&mut self) -> Result<Stmt> {
+        let label = self.advance();
+        consume_next!(self, TokenType::Colon, "Expect ':' after label");
+        if match_next!(self, TokenType::For) {
+            return self.parse_for_statement(Some(label));
+        }
+        if match_next!(self, TokenType::While) {
+            return self.parse_while_loop(Some(label));
+        }
+        if match_next!(self, TokenType::Do) {
+            return self.parse_do_while_loop(Some(label));
+        }
+        if match_next!(self, TokenType::Repeat) {
+            return self.parse_repeat_loop(Some(label));
+        }
+        Err(self.error("Expect loop after label"))
+    }
+
+    fn parse_loop_body(&mut self, label: Option<&Token>) -> Result<Stmt> {
+        self.loops.push(label.map(|label| label.text.clone()));
+        let body = self.nested(Self::parse_statement);
+        self.loops.pop();
+        body
     }
 
-    fn parse_for_statement(&mut self) -> Result<Stmt> {
+    fn parse_for_statement(&mut self, label: Option<Token>) -> Result<Stmt> {
         consume_next!(self, TokenType::LeftParen, "Expect '(' after 'for'.");
-        let initializer = match self.peek().token_type {
+        let initializer = match self.peek_type() {
             TokenType::Var => {
                 self.advance();
                 Some(self.parse_variable_declaration()?)
@@ -99,11 +262,9 @@ impl<'a> Parser<'a> {
             _ => Some(self.parse_expression_statement()?),
         };
 
-        let condition = match self.peek().token_type {
-            TokenType::Semicolon => Expr::Literal {
-                value: Token::new(TokenType::True, "true".to_string(), self.peek().line),
-            },
-            _ => self.parse_expression()?,
+        let condition = match self.peek_type() {
+            TokenType::Semicolon => None,
+            _ => Some(self.parse_expression()?),
         };
         consume_next!(
             self,
@@ -111,36 +272,20 @@ impl<'a> Parser<'a> {
             "Expect ';' after loop condition."
         );
 
-        let increment = match self.peek().token_type {
+        let increment = match self.peek_type() {
             TokenType::RightParen => None,
             _ => Some(self.parse_expression()?),
         };
         consume_next!(self, TokenType::RightParen, "Expect ')' after for clauses.");
-        let mut body = self.parse_statement()?;
-
-        if let Some(increment) = increment {
-            body = Stmt::Block {
-                stmts: vec![
-                    body,
-                    Stmt::Expression {
-                        expr: Box::new(increment),
-                    },
-                ],
-            }
-        }
+        let body = self.parse_loop_body(label.as_ref())?;
 
-        body = Stmt::While {
-            condition: Box::new(condition),
+        Ok(Stmt::For {
+            initializer: initializer.map(Box::new),
+            condition: condition.map(Box::new),
+            increment: increment.map(Box::new),
             body: Box::new(body),
-        };
-
-        if let Some(initializer) = initializer {
-            body = Stmt::Block {
-                stmts: vec![initializer, body],
-            }
-        }
-
-        Ok(body)
+            label,
+        })
     }
 
     fn parse_if_statement(&mut self) -> Result<Stmt> {
@@ -152,11 +297,11 @@ impl<'a> Parser<'a> {
             "Expect ')' after if condition."
         );
 
-        let then_branch = self.parse_statement()?;
+        let then_branch = self.nested(Self::parse_statement)?;
         let mut else_branch = None;
 
         if match_next!(self, TokenType::Else) {
-            else_branch = Some(self.parse_statement()?);
+            else_branch = Some(self.nested(Self::parse_statement)?);
         }
         Ok(Stmt::If {
             condition: Box::new(condition),
@@ -165,7 +310,64 @@ impl<'a> Parser<'a> {
         })
     }
 
-    fn parse_while_loop(&mut self) -> Result<Stmt> {
+    fn parse_switch_statement(&mut self) -> Result<Stmt> {
+        consume_next!(self, TokenType::LeftParen, "Expect '(' after 'switch'.");
+        let discriminant = self.parse_expression()?;
+        consume_next!(
+            self,
+            TokenType::RightParen,
+            "Expect ')' after switch value."
+        );
+        consume_next!(self, TokenType::LeftBrace, "Expect '{' before switch body.");
+
+        self.switches += 1;
+        let cases = self.nested(Self::parse_switch_cases);
+        self.switches -= 1;
+        let (cases, default) = cases?;
+        consume_next!(self, TokenType::RightBrace, "Expect '}' after switch body.");
+
+        Ok(Stmt::Switch {
+            discriminant: Box::new(discriminant),
+            cases,
+            default,
+        })
+    }
+
+    fn parse_switch_cases(&mut self) -> Result<(Vec<SwitchCase>, Option<Vec<Stmt>>)> {
+        let mut cases = vec![];
+        let mut default = None;
+        loop {
+            if match_next!(self, TokenType::Case) {
+                let value = self.parse_expression()?;
+                consume_next!(self, TokenType::Colon, "Expect ':' after case value.");
+                let body = self.parse_case_body()?;
+                cases.push(SwitchCase { value, body });
+            } else if match_next!(self, TokenType::Default) {
+                if default.is_some() {
+                    return Err(self.error("Switch can only have one default case."));
+                }
+                consume_next!(self, TokenType::Colon, "Expect ':' after 'default'.");
+                default = Some(self.parse_case_body()?);
+            } else {
+                break;
+            }
+        }
+        Ok((cases, default))
+    }
+
+    fn parse_case_body(&mut self) -> Result<Vec<Stmt>> {
+        let mut stmts = vec![];
+        while !matches!(
+            self.peek_type(),
+            TokenType::Case | TokenType::Default | TokenType::RightBrace
+        ) && !self.at_end()
+        {
+            stmts.extend(self.parse_declaration_recovering());
+        }
+        Ok(stmts)
+    }
+
+    fn parse_while_loop(&mut self, label: Option<Token>) -> Result<Stmt> {
         consume_next!(self, TokenType::LeftParen, "Expect '(' after 'if'.");
         let condition = self.parse_expression()?;
         consume_next!(
@@ -173,21 +375,91 @@ impl<'a> Parser<'a> {
             TokenType::RightParen,
             "Expect ')' after if condition."
         );
-        let body = self.parse_statement()?;
+        let body = self.parse_loop_body(label.as_ref())?;
         Ok(Stmt::While {
             condition: Box::new(condition),
             body: Box::new(body),
+            label,
         })
     }
 
-    fn parse_print_statement(&mut self) -> Result<Stmt> {
-        let expr = self.parse_expression()?;
-        consume_next!(self, TokenType::Semicolon, "Expect ; after print statement");
-        Ok(Stmt::Print {
-            expr: Box::new(expr),
+    fn parse_do_while_loop(&mut self, label: Option<Token>) -> Result<Stmt> {
+        let body = self.parse_loop_body(label.as_ref())?;
+        consume_next!(self, TokenType::While, "Expect 'while' after 'do' body.");
+        consume_next!(self, TokenType::LeftParen, "Expect '(' after 'while'.");
+        let condition = self.parse_expression()?;
+        consume_next!(
+            self,
+            TokenType::RightParen,
+            "Expect ')' after while condition."
+        );
+        consume_next!(
+            self,
+            TokenType::Semicolon,
+            "Expect ';' after do-while loop."
+        );
+        Ok(Stmt::DoWhile {
+            body: Box::new(body),
+            condition: Box::new(condition),
+            label,
         })
     }
 
+    fn parse_repeat_loop(&mut self, label: Option<Token>) -> Result<Stmt> {
+        let keyword = self.previous();
+        consume_next!(self, TokenType::LeftParen, "Expect '(' after 'repeat'.");
+        let count = self.parse_expression()?;
+        consume_next!(
+            self,
+            TokenType::RightParen,
+            "Expect ')' after repeat count."
+        );
+        let body = self.parse_loop_body(label.as_ref())?;
+        Ok(Stmt::Repeat {
+            keyword,
+            count: Box::new(count),
+            body: Box::new(body),
+            label,
+        })
+    }
+
+    fn parse_break(&mut self) -> Result<Stmt> {
+        let keyword = self.previous();
+        let label = self.parse_jump_label("break")?;
+        consume_next!(self, TokenType::Semicolon, "Expect ';' after 'break'");
+        Ok(Stmt::Break { keyword, label })
+    }
+
+    fn parse_continue(&mut self) -> Result<Stmt> {
+        let keyword = self.previous();
+        let label = self.parse_jump_label("continue")?;
+        consume_next!(self, TokenType::Semicolon, "Expect ';' after 'continue'");
+        Ok(Stmt::Continue { keyword, label })
+    }
+
+    fn parse_jump_label(&mut self, keyword: &str) -> Result<Option<Token>> {
+        if self.loops.is_empty() && !(keyword == "break" && self.switches > 0) {
+            return Err(self.error(&format!("Can't use '{}' outside of a loop", keyword)));
+        }
+        if !match_next!(self, TokenType::Identifier) {
+            return Ok(None);
+        }
+        let label = self.previous();
+        if !self.loops.contains(&Some(label.text.clone())) {
+            return Err(self.error(&format!("Undefined loop label '{}'", label.text)));
+        }
+        Ok(Some(label))
+    }
+
+    fn parse_print_statement(&mut self) -> Result<Stmt> {
+        let mut exprs = vec![self.parse_expression()?];
+        while match_next!(self, TokenType::Comma) {
+            exprs.push(self.parse_expression()?);
+        }
+        self.consume_semicolon("Expect ; after print statement")?;
+        Ok(Stmt::Print { exprs })
+    }
+
     fn parse_function_declaration(&mut self, kind: &str) -> Result<Stmt> {
         let name = consume_next!(
             self,
@@ -201,16 +473,26 @@ impl<'a> Parser<'a> {
         );
 
         let mut params = vec![];
-        if !matches!(self.peek().token_type, TokenType::RightParen) {
+        if !matches!(self.peek_type(), TokenType::RightParen) {
             loop {
                 if params.len() >= 255 {
                     return Err(self.error("Can't have more than 255 parameters"));
                 }
-                params.push(consume_next!(
-                    self,
-                    TokenType::Identifier,
-                    "Expect parameter name"
-                ));
+                let name = consume_next!(self, TokenType::Identifier, "Expect parameter name");
+                let default = if match_next!(self, TokenType::Equal) {
+                    Some(self.parse_expression()?)
+                } else {
+                    None
+                };
+                let follows_default = params
+                    .last()
+                    .is_some_and(|param: &Parameter| param.default.is_some());
+                if default.is_none() && follows_default {
+                    return Err(
+                        self.error("Parameter without a default can't follow one with a default")
+                    );
+                }
+                params.push(Parameter { name, default });
                 if !match_next!(self, TokenType::Comma) {
                     break;
                 }
@@ -224,20 +506,28 @@ impl<'a> Parser<'a> {
             &format!("Expect '{{' before {} body", kind)
         );
 
-        let Ok(Stmt::Block { stmts }) = self.parse_block() else {
-            return Err(self.error("Expect block to have statements"));
-        };
+        // Loops outside the function body can't be targeted from inside it.
+        let enclosing_loops = std::mem::take(&mut self.loops);
+        let enclosing_switches = std::mem::take(&mut self.switches);
+        let body = self.nested(|parser| parser.parse_block_body(true));
+        self.loops = enclosing_loops;
+        self.switches = enclosing_switches;
+        // A trailing expression without a `;` is the implicit return value.
+        let (mut body, value) = body?;
+        body.extend(value.map(|expr| Stmt::Expression {
+            expr: Box::new(expr),
+        }));
 
         Ok(Stmt::FunctionDeclaration(FunctionDeclaration {
             name,
             params,
-            body: stmts,
+            body,
         }))
     }
 
     fn parse_return(&mut self) -> Result<Stmt> {
         let keyword = self.previous();
-        let value = match self.peek().token_type {
+        let value = match self.peek_type() {
             TokenType::Semicolon => None,
             _ => Some(Box::new(self.parse_expression()?)),
         };
@@ -247,49 +537,103 @@ impl<'a> Parser<'a> {
         Ok(Stmt::Return { keyword, value })
     }
 
-    fn parse_variable_declaration(&mut self) -> Result<Stmt> {
-        let name = consume_next!(self, TokenType::Identifier, "Expect variable name");
-        let initializer = match self.peek().token_type {
-            TokenType::Equal => {
-                self.advance();
-                let initializer = Some(Box::new(self.parse_expression()?));
-                consume_next!(self, TokenType::Semicolon, "Expect ';' after expression");
-                initializer
-            }
+    fn parse_throw(&mut self) -> Result<Stmt> {
+        let keyword = self.previous();
+        let value = self.parse_expression()?;
+        consume_next!(self, TokenType::Semicolon, "Expect ';' after thrown value");
+        Ok(Stmt::Throw {
+            keyword,
+            value: Box::new(value),
+        })
+    }
 
-            TokenType::Semicolon => {
-                self.advance();
+    fn parse_variable_declaration(&mut self) -> Result<Stmt> {
+        let mut declarations = vec![];
+        loop {
+            let name = consume_next!(self, TokenType::Identifier, "Expect variable name");
+            let initializer = if match_next!(self, TokenType::Equal) {
+                Some(Box::new(self.parse_expression()?))
+            } else {
                 None
+            };
+            declarations.push(Stmt::VariableDeclaration { name, initializer });
+            if !match_next!(self, TokenType::Comma) {
+                break;
             }
-            _ => {
-                return Err(self.error("Expect ; after variable declaration"));
-            }
-        };
-        Ok(Stmt::VariableDeclaration { name, initializer })
+        }
+        consume_next!(
+            self,
+            TokenType::Semicolon,
+            "Expect ';' after variable declaration"
+        );
+        if declarations.len() == 1 {
+            return Ok(declarations.remove(0));
+        }
+        Ok(Stmt::Sequence {
+            stmts: declarations,
+        })
     }
 
-    fn parse_expression_statement(&mut self) -> Result<Stmt> {
-        let expr = self.parse_expression()?;
+    fn parse_const_declaration(&mut self) -> Result<Stmt> {
+        let name = consume_next!(self, TokenType::Identifier, "Expect constant name");
+        consume_next!(self, TokenType::Equal, "Expect '=' after constant name");
+        let initializer = Box::new(self.parse_expression()?);
         consume_next!(
             self,
             TokenType::Semicolon,
-            "Expect ; after expression statement"
+            "Expect ';' after constant declaration"
         );
+        Ok(Stmt::ConstDeclaration { name, initializer })
+    }
+
+    fn parse_expression_statement(&mut self) -> Result<Stmt> {
+        let line = self.peek().line;
+        let expr = self.parse_expression()?;
+        self.consume_semicolon("Expect ; after expression statement")?;
+        self.warnings.extend(unused_value(&expr, line));
         Ok(Stmt::Expression {
             expr: Box::new(expr),
         })
     }
 
+    // Parses one expression and stops, leaving any tokens after it for the caller.
     fn parse_expression(&mut self) -> Result<Expr> {
-        self.parse_assignment()
+        self.nested(Self::parse_assignment)
     }
 
     fn parse_assignment(&mut self) -> Result<Expr> {
-        let expr = self.parse_logic_or()?;
+        let expr = self.parse_coalesce()?;
+
+        if self.at_logical_assignment(TokenType::Or) || self.at_logical_assignment(TokenType::And) {
+            let operator = self.advance();
+            self.advance();
+            let value = self.nested(Self::parse_assignment)?;
+            let Expr::Variable { ref name } = expr else {
+                return Err(self.error("Invalid assignment target"));
+            };
+            // `x or= y` is `x or (x = y)`.
+            let left = Box::new(expr.clone());
+            let right = Box::new(Expr::Assignment {
+                name: name.clone(),
+                value: Box::new(value),
+            });
+            return Ok(match operator.token_type {
+                TokenType::Or => Expr::LogicOr {
+                    left,
+                    operator,
+                    right,
+                },
+                _ => Expr::LogicAnd {
+                    left,
+                    operator,
+                    right,
+                },
+            });
+        }
 
         if match_next!(self, TokenType::Equal) {
             let _equal = self.previous();
-            let value = self.parse_assignment()?;
+            let value = self.nested(Self::parse_assignment)?;
             if let Expr::Variable { ref name } = &expr {
                 return Ok(Expr::Assignment {
                     name: name.clone(),
@@ -301,9 +645,23 @@ impl<'a> Parser<'a> {
         Ok(expr)
     }
 
+    fn parse_coalesce(&mut self) -> Result<Expr> {
+        let mut expr = self.parse_logic_or()?;
+        while match_next!(self, TokenType::QuestionQuestion) {
+            let operator = self.previous();
+            let right = self.parse_logic_or()?;
+            expr = Expr::Coalesce {
+                left: Box::new(expr),
+                operator,
+                right: Box::new(right),
+            };
+        }
+        Ok(expr)
+    }
+
     fn parse_logic_or(&mut self) -> Result<Expr> {
         let mut expr = self.parse_logic_and()?;
-        while match_next!(self, TokenType::Or) {
+        while !self.at_logical_assignment(TokenType::Or) && match_next!(self, TokenType::Or) {
             let operator = self.previous();
             let right = self.parse_logic_and()?;
             expr = Expr::LogicOr {
@@ -317,7 +675,7 @@ impl<'a> Parser<'a> {
 
     fn parse_logic_and(&mut self) -> Result<Expr> {
         let mut expr = self.parse_equality()?;
-        while match_next!(self, TokenType::And) {
+        while !self.at_logical_assignment(TokenType::And) && match_next!(self, TokenType::And) {
             let operator = self.previous();
             let right = self.parse_equality()?;
             expr = Expr::LogicAnd {
@@ -329,12 +687,16 @@ impl<'a> Parser<'a> {
         Ok(expr)
     }
 
+    fn at_logical_assignment(&self, operator: TokenType) -> bool {
+        *self.peek_type() == operator && matches!(self.peek_next().token_type, TokenType::Equal)
+    }
+
     fn parse_equality(&mut self) -> Result<Expr> {
-        let mut expr = self.parse_comparison()?;
+        let mut expr = self.parse_type_test()?;
 
         while match_next!(self, TokenType::BangEqual | TokenType::EqualEqual) {
             let operator = self.previous();
-            let right = self.parse_comparison()?;
+            let right = self.parse_type_test()?;
 
             expr = Expr::Binary {
                 left: Box::new(expr),
@@ -346,6 +708,24 @@ impl<'a> Parser<'a> {
         Ok(expr)
     }
 
+    fn parse_type_test(&mut self) -> Result<Expr> {
+        let mut expr = self.parse_comparison()?;
+        while match_next!(self, TokenType::Is) {
+            let operator = self.previous();
+            let type_name = consume_next!(
+                self,
+                TokenType::Identifier | TokenType::Nil,
+                "Expect type name after 'is'"
+            );
+            expr = Expr::TypeTest {
+                value: Box::new(expr),
+                operator,
+                type_name,
+            };
+        }
+        Ok(expr)
+    }
+
     fn parse_comparison(&mut self) -> Result<Expr> {
         let mut expr = self.parse_term()?;
 
@@ -354,6 +734,20 @@ impl<'a> Parser<'a> {
             TokenType::Greater | TokenType::GreaterEqual | TokenType::Less | TokenType::LessEqual
         ) {
             let operator = self.previous();
+            // `1 < x < 3` always fails at runtime.
+            if let Expr::Binary {
+                operator: ref previous,
+                ..
+            } = expr
+            {
+                if is_comparison(&previous.token_type) {
+                    let msg = format!(
+                        "Comparisons can't be chained, write 'a {0} b and b {1} c' instead of 'a {0} b {1} c'",
+                        previous.text, operator.text
+                    );
+                    self.errors.push(self.error(&msg));
+                }
+            }
             let right = self.parse_term()?;
 
             expr = Expr::Binary {
@@ -400,7 +794,7 @@ impl<'a> Parser<'a> {
     fn parse_unary(&mut self) -> Result<Expr> {
         if match_next!(self, TokenType::Bang | TokenType::Minus) {
             let operator = self.previous();
-            let right = self.parse_unary()?;
+            let right = self.nested(Self::parse_unary)?;
 
             return Ok(Expr::Unary {
                 operator,
@@ -425,7 +819,7 @@ impl<'a> Parser<'a> {
 
     fn finish_call(&mut self, callee: Expr) -> Result<Expr> {
         let mut args = vec![];
-        if !matches!(self.peek().token_type, TokenType::RightParen) {
+        if !matches!(self.peek_type(), TokenType::RightParen) {
             loop {
                 if args.len() >= 255 {
                     return Err(self.error("Can't have more than 255 arguments"));
@@ -447,19 +841,21 @@ impl<'a> Parser<'a> {
     }
 
     fn parse_primary(&mut self) -> Result<Expr> {
-        match self.peek().token_type {
-            TokenType::True
-            | TokenType::False
-            | TokenType::Number(..)
-            | TokenType::String(..)
-            | TokenType::Nil => {
-                let value = self.advance();
-                Ok(Expr::Literal { value })
-            }
+        match self.peek_type() {
+            TokenType::True => self.parse_literal(Literal::Boolean(true)),
+            TokenType::False => self.parse_literal(Literal::Boolean(false)),
+            TokenType::Nil => self.parse_literal(Literal::Nil),
+            TokenType::Number(num) => self.parse_literal(Literal::Number(*num)),
+            TokenType::String(string) => self.parse_literal(Literal::String(string.clone())),
             TokenType::Identifier => {
                 let name = self.advance();
                 Ok(Expr::Variable { name })
             }
+            TokenType::Interpolation(..) => self.parse_interpolation(),
+            TokenType::LeftBrace => {
+                self.advance();
+                self.nested(Self::parse_block_expression)
+            }
             TokenType::LeftParen => {
                 self.advance();
                 let expr = self.parse_expression()?;
@@ -472,6 +868,119 @@ impl<'a> Parser<'a> {
         }
     }
 
+    fn parse_literal(&mut self, value: Literal) -> Result<Expr> {
+        self.advance();
+        Ok(Expr::Literal { value })
+    }
+
+    // `"a ${x} b"` arrives as Interpolation("a "), the tokens of `x`, then String(" b").
+    fn parse_interpolation(&mut self) -> Result<Expr> {
+        let mut parts = vec![];
+        while let TokenType::Interpolation(text) = self.peek().token_type {
+            self.advance();
+            parts.push(Expr::Literal {
+                value: Literal::String(text),
+            });
+            parts.push(self.parse_expression()?);
+        }
+        let TokenType::String(text) = self.peek().token_type else {
+            return Err(self.error("Expect '}' after interpolated expression"));
+        };
+        self.advance();
+        parts.push(Expr::Literal {
+            value: Literal::String(text),
+        });
+        Ok(Expr::Interpolation { parts })
+    }
+
+    fn nested<T>(&mut self, parse: impl FnOnce(&mut Self) -> Result<T>) -> Result<T> {
+        if self.depth >= self.max_depth {
+            let msg = format!("Code nested too deeply (limit is {})", self.max_depth);
+            self.too_deep = true;
+            return Err(self.error(&msg));
+        }
+        self.depth += 1;
+        let result = parse(self);
+        self.depth -= 1;
+        result
+    }
+
+    fn parse_block_expression(&mut self) -> Result<Expr> {
+        let (stmts, value) = self.parse_block_body(false)?;
+        Ok(Expr::Block {
+            stmts,
+            value: value.map(Box::new),
+        })
+    }
+
+    // An expression followed by the closing brace instead of a `;` is the block's value.
+    fn parse_block_body(&mut self, returns_last: bool) -> Result<(Vec<Stmt>, Option<Expr>)> {
+        let mut stmts = vec![];
+        let mut value = None;
+        let mut unused = None;
+        while !matches!(self.peek_type(), TokenType::RightBrace) && !self.at_end() {
+            self.warnings.extend(unused.take());
+            if self.at_statement_start() {
+                stmts.extend(self.parse_declaration_recovering());
+                continue;
+            }
+            let line = self.peek().line;
+            let expr = self.parse_expression()?;
+            if !match_next!(self, TokenType::Semicolon) {
+                value = Some(expr);
+                break;
+            }
+            unused = unused_value(&expr, line);
+            stmts.push(Stmt::Expression {
+                expr: Box::new(expr),
+            });
+        }
+        if !returns_last || value.is_some() {
+            self.warnings.extend(unused);
+        }
+        consume_next!(self, TokenType::RightBrace, "Expect } after block");
+        Ok((stmts, value))
+    }
+
+    // A missing `;` is reported but parsing carries on as if it were there.
+    fn consume_semicolon(&mut self, msg: &str) -> Result<()> {
+        if match_next!(self, TokenType::Semicolon) {
+            return Ok(());
+        }
+        if self.peek().line > self.previous().line
+            || matches!(self.peek_type(), TokenType::RightBrace)
+            || self.at_statement_start()
+            || self.at_end()
+        {
+            self.errors.push(self.error(msg));
+            return Ok(());
+        }
+        Err(self.error(msg))
+    }
+
+    fn at_statement_start(&self) -> bool {
+        match self.peek_type() {
+            TokenType::Fun
+            | TokenType::Return
+            | TokenType::Var
+            | TokenType::Const
+            | TokenType::For
+            | TokenType::If
+            | TokenType::Switch
+            | TokenType::While
+            | TokenType::Do
+            | TokenType::Repeat
+            | TokenType::Break
+            | TokenType::Continue
+            | TokenType::Throw
+            | TokenType::Try
+            | TokenType::Print
+            | TokenType::LeftBrace => true,
+            TokenType::Identifier => matches!(self.peek_next().token_type, TokenType::Colon),
+            _ => false,
+        }
+    }
+
     fn advance(&mut self) -> Token {
         if !self.at_end() {
             self.current += 1;
@@ -487,14 +996,296 @@ impl<'a> Parser<'a> {
         self.tokens[self.current].clone()
     }
 
+    fn peek_type(&self) -> &TokenType {
+        &self.tokens[self.current].token_type
+    }
+
+    fn peek_next(&self) -> Token {
+        if self.at_end() {
+            return self.peek();
+        }
+        self.tokens[self.current + 1].clone()
+    }
+
     fn at_end(&self) -> bool {
-        matches!(self.peek().token_type, TokenType::Eof)
+        matches!(self.peek_type(), TokenType::Eof)
     }
 
     fn error(&self, msg: &str) -> Error {
+        // Nothing has been consumed yet when the very first token is rejected.
+        let token = if self.current == 0 {
+            self.peek()
+        } else {
+            self.previous()
+        };
         Error::ParseError {
-            line: self.previous().line,
+            file: None,
+            line: token.line,
             msg: msg.into(),
         }
     }
 }
+
+fn is_comparison(token_type: &TokenType) -> bool {
+    matches!(
+        token_type,
+        TokenType::Greater | TokenType::GreaterEqual | TokenType::Less | TokenType::LessEqual
+    )
+}
+
+fn unused_value(expr: &Expr, line: usize) -> Option<Warning> {
+    (!has_effects(expr)).then(|| Warning {
+        line,
+        msg: "Expression result is unused".to_string(),
+    })
+}
+
+fn has_effects(expr: &Expr) -> bool {
+    match expr {
+        Expr::Literal { .. } | Expr::Variable { .. } => false,
+        Expr::Grouping { expr } => has_effects(expr),
+        Expr::Unary { right, .. } => has_effects(right),
+        Expr::TypeTest { value, .. } => has_effects(value),
+        Expr::Binary { left, right, .. }
+        | Expr::LogicOr { left, right, .. }
+        | Expr::LogicAnd { left, right, .. }
+        | Expr::Coalesce { left, right, .. } => has_effects(left) || has_effects(right),
+        Expr::Interpolation { parts } => parts.iter().any(has_effects),
+        Expr::Call { .. } | Expr::Assignment { .. } | Expr::Block { .. } => true,
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::scanner::Scanner;
+
+    fn parse(source: &str) -> Result<Vec<Stmt>> {
+        let mut scanner = Scanner::new(source.to_string());
+        let tokens = scanner.scan_tokens()?;
+        Parser::new(tokens).parse()
+    }
+
+    // The depth limit is sized for the main thread's 8 MiB stack, not a test thread's 2 MiB.
+    fn with_main_stack(test: impl FnOnce() + Send + 'static) {
+        std::thread::Builder::new()
+            .stack_size(8 << 20)
+            .spawn(test)
+            .unwrap()
+            .join()
+            .unwrap();
+    }
+
+    fn assert_too_deep(source: String) {
+        with_main_stack(move || {
+            let err = parse(&source).unwrap_err();
+            assert!(err.to_string().contains("nested too deeply"), "{err}");
+        });
+    }
+
+    #[test]
+    fn deep_grouping_is_rejected() {
+        let depth = 100_000;
+        assert_too_deep(format!("{}1{};", "(".repeat(depth), ")".repeat(depth)));
+    }
+
+    #[test]
+    fn deep_blocks_are_rejected_once() {
+        let depth = 100_000;
+        let blocks = format!("{}{}", "{".repeat(depth), "}".repeat(depth));
+        assert_too_deep(format!("var x = {blocks};"));
+        assert_too_deep(blocks.clone());
+        with_main_stack(move || {
+            let err = parse(&blocks).unwrap_err();
+            assert!(matches!(err, Error::ParseError { .. }), "{err}");
+        });
+        assert!(parse("var x = { { print 1; } 2 }; { { print x; } }").is_ok());
+    }
+
+    #[test]
+    fn unbraced_statement_nesting_is_rejected() {
+        let depth = 100_000;
+        assert_too_deep(format!("{}print 1;", "if (true) ".repeat(depth)));
+        assert_too_deep(format!("{}print 1;", "while (false) ".repeat(depth)));
+        assert_too_deep(format!("{}print 1;", "for (;;) ".repeat(depth)));
+        assert_too_deep(format!(
+            "{}1{}",
+            "fun f() {".repeat(depth),
+            "}".repeat(depth)
+        ));
+        assert_too_deep(format!(
+            "{}{}",
+            "try {".repeat(depth),
+            "} catch (e) {}".repeat(depth)
+        ));
+        let switches = "switch (1) { case 1: ".repeat(depth);
+        assert_too_deep(format!("{switches}print 1;{}", " }".repeat(depth)));
+        assert!(parse(&format!("{}print 1;", "if (true) ".repeat(50))).is_ok());
+    }
+
+    #[test]
+    fn assignment_chains_count_towards_depth() {
+        assert_too_deep(format!("var a; a = {}1;", "a = ".repeat(2_000)));
+        assert_too_deep(format!("var a; a = {}1;", "a or= ".repeat(2_000)));
+        assert!(parse(&format!("var a; a = {}1;", "a = ".repeat(50))).is_ok());
+    }
+
+    #[test]
+    fn parses_hand_built_tokens() {
+        let token = |token_type, text: &str| Token::new(token_type, text.to_string(), 1, 0, 0);
+        let tokens = vec![
+            token(TokenType::Var, "var"),
+            token(TokenType::Identifier, "x"),
+            token(TokenType::Equal, "="),
+            token(TokenType::Number(1.0), "1"),
+            token(TokenType::Plus, "+"),
+            token(TokenType::Number(2.0), "2"),
+            token(TokenType::Semicolon, ";"),
+            token(TokenType::Eof, ""),
+        ];
+        let stmts = Parser::new(&tokens).parse().unwrap();
+
+        let mut interpreter = crate::interpreter::Interpreter::new();
+        interpreter.interpret(stmts).unwrap();
+        let x = interpreter.interpret_expression(&crate::parse_expr("x").unwrap());
+        assert_eq!(x.unwrap(), crate::interpreter::Value::Number(3.0));
+    }
+
+    #[test]
+    fn discarded_call_results_are_not_warned_about() {
+        let source = "fun f() { return 1; } fun g() {} f(); g(); 1 + 2;";
+        let mut scanner = Scanner::new(source.to_string());
+        let tokens = scanner.scan_tokens().unwrap();
+        let mut parser = Parser::new(tokens);
+        let stmts = parser.parse().unwrap();
+        // Only the arithmetic on its own is flagged.
+        let warnings: Vec<&str> = parser.warnings().iter().map(|w| w.msg.as_str()).collect();
+        assert_eq!(warnings, ["Expression result is unused"]);
+
+        let mut interpreter = crate::interpreter::Interpreter::new();
+        assert!(interpreter.interpret(stmts).is_ok());
+    }
+
+    #[test]
+    fn parse_expr_parses_exactly_one_expression() {
+        let Expr::Binary {
+            operator, right, ..
+        } = crate::parse_expr("1 + 2 * 3").unwrap()
+        else {
+            panic!("Expected a binary expression");
+        };
+        assert_eq!(operator.text, "+");
+        assert!(matches!(*right, Expr::Binary { ref operator, .. } if operator.text == "*"));
+
+        assert!(crate::parse_expr("1 + 2 3").is_err());
+        // Errors recorded on the way, rather than returned, still fail the parse.
+        assert!(crate::parse_expr("1 < 2 < 3").is_err());
+        assert!(crate::parse_expr("{ var = 1; 2 }").is_err());
+    }
+
+    #[test]
+    fn recovers_to_report_every_error() {
+        let err = parse("var = 1;\nprint 2\nvar x = ;\nprint 3;").unwrap_err();
+        let Error::Multiple(errors) = err else {
+            panic!("Expected several errors, got {err}");
+        };
+        let lines: Vec<usize> = errors
+            .iter()
+            .map(|err| match err {
+                Error::ParseError { line, .. } => *line,
+                err => panic!("Expected a parse error, got {err}"),
+            })
+            .collect();
+        assert_eq!(lines, [1, 2, 3]);
+    }
+
+    #[test]
+    fn chained_comparisons_are_rejected_with_a_hint() {
+        let err = parse("var x = 2;\nprint 1 < x < 3;").unwrap_err();
+        assert!(matches!(err, Error::ParseError { line: 2, .. }), "{err}");
+        assert!(err.to_string().contains("'a < b and b < c'"), "{err}");
+        assert!(parse("print 1 < 2 and 2 < 3; print (1 < 2) == true;").is_ok());
+    }
+
+    #[test]
+    fn print_takes_a_list_of_expressions() {
+        let stmts = parse("print 1, \"two\", 1 + 2;").unwrap();
+        assert!(matches!(&stmts[..], [Stmt::Print { exprs }] if exprs.len() == 3));
+        assert!(parse("print;").is_err());
+        assert!(parse("print 1,;").is_err());
+    }
+
+    #[test]
+    fn errors_on_the_first_token_are_reported_without_panicking() {
+        for source in ["this();", "super();", ")", "="] {
+            let err = parse(source).unwrap_err();
+            assert!(err.to_string().contains("[line 1]"), "{source}: {err}");
+        }
+    }
+
+    #[test]
+    fn undefined_loop_labels_are_rejected() {
+        let err = parse("outer: while (true) {\n  while (true) { break inner; }\n}").unwrap_err();
+        assert!(matches!(err, Error::ParseError { line: 2, .. }), "{err}");
+        assert!(
+            err.to_string().contains("Undefined loop label 'inner'"),
+            "{err}"
+        );
+        assert!(parse("a: while (true) {} b: while (true) { continue a; }").is_err());
+        assert!(parse("a: while (true) { b: while (true) { break a; } }").is_ok());
+    }
+
+    #[test]
+    fn moderate_grouping_evaluates_normally() {
+        let mut interpreter = crate::interpreter::Interpreter::new();
+        let expr = crate::parse_expr("(((1 + 2)))").unwrap();
+        let value = interpreter.interpret_expression(&expr).unwrap();
+        assert_eq!(value, crate::interpreter::Value::Number(3.0));
+
+        with_main_stack(|| {
+            let depth = 100;
+            let expr = format!("{}1 + 2{}", "(".repeat(depth), ")".repeat(depth));
+            let mut interpreter = crate::interpreter::Interpreter::new();
+            let value = interpreter.interpret_expression(&crate::parse_expr(&expr).unwrap());
+            assert_eq!(value.unwrap(), crate::interpreter::Value::Number(3.0));
+        });
+    }
+
+    #[test]
+    fn token_dense_source_parses_as_before() {
+        let step = "x=x+(1+2)*-3-4/2*-(1-2)+f(x,1)-f(x,1);b=x<0==true and x>=-5500 or false;";
+        let source = format!(
+            "fun f(a,b){{return a+b;}}var x=0;var b;{}",
+            step.repeat(500)
+        );
+        let stmts = parse(&source).unwrap();
+        assert_eq!(stmts.len(), 1003);
+
+        let mut interpreter = crate::interpreter::Interpreter::new();
+        interpreter.interpret(stmts).unwrap();
+        let x = interpreter.interpret_expression(&crate::parse_expr("x").unwrap());
+        assert_eq!(x.unwrap(), crate::interpreter::Value::Number(-5500.0));
+        let b = interpreter.interpret_expression(&crate::parse_expr("b").unwrap());
+        assert_eq!(b.unwrap(), crate::interpreter::Value::Boolean(true));
+    }
+
+    #[test]
+    fn pure_expression_statements_are_warned_about() {
+        let warnings = |source: &str| {
+            let mut scanner = Scanner::new(source.to_string());
+            let tokens = scanner.scan_tokens().unwrap();
+            let mut parser = Parser::new(tokens);
+            parser.parse().unwrap();
+            parser.warnings().to_vec()
+        };
+        let unused = |line| Warning {
+            line,
+            msg: "Expression result is unused".to_string(),
+        };
+        let source = "var x = 0;\nfun f() {}\n1 + 2;\nx;\nf();\nx = 1;\n-(x);\n\"${x}\";\nx + f();";
+        assert_eq!(
+            warnings(source),
+            [unused(3), unused(4), unused(7), unused(8)]
+        );
+    }
+}
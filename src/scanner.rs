@@ -1,7 +1,27 @@
 use crate::token::*;
-use std::collections::HashMap;
+use crate::{Error, Result};
+
+const ESCAPES: [char; 7] = ['n', 'r', 't', '0', '"', '\\', '$'];
+
+// Writes a string back as the inside of a literal.
+pub fn escape(string: &str) -> String {
+    let mut out = String::new();
+    let mut chars = string.chars().peekable();
+    while let Some(ch) = chars.next() {
+        match ch {
+            '"' => out.push_str("\\\""),
+            '\\' => out.push_str("\\\\"),
+            '\n' => out.push_str("\\n"),
+            '\r' => out.push_str("\\r"),
+            '\t' => out.push_str("\\t"),
+            '\0' => out.push_str("\\0"),
+            '$' if chars.peek() == Some(&'{') => out.push_str("\\$"),
+            ch => out.push(ch),
+        }
+    }
+    out
+}
 
-#[derive(Default)]
 pub struct Scanner {
     source: Vec<char>,
     tokens: Vec<Token>,
@@ -10,45 +30,63 @@ pub struct Scanner {
     current: usize,
     line: usize,
 
-    keywords: HashMap<String, TokenType>,
+    errors: Vec<Error>,
+    // Open braces inside each `${ ... }` still being scanned, innermost last.
+    interpolations: Vec<usize>,
+}
+
+impl Default for Scanner {
+    fn default() -> Self {
+        Self::new(String::new())
+    }
 }
 
 impl Scanner {
     pub fn new(source: String) -> Self {
-        let keywords = HashMap::from([
-            ("and".to_string(), TokenType::And),
-            ("class".to_string(), TokenType::Class),
-            ("else".to_string(), TokenType::Else),
-            ("false".to_string(), TokenType::False),
-            ("for".to_string(), TokenType::For),
-            ("fun".to_string(), TokenType::Fun),
-            ("if".to_string(), TokenType::If),
-            ("nil".to_string(), TokenType::Nil),
-            ("or".to_string(), TokenType::Or),
-            ("print".to_string(), TokenType::Print),
-            ("return".to_string(), TokenType::Return),
-            ("super".to_string(), TokenType::Super),
-            ("this".to_string(), TokenType::This),
-            ("true".to_string(), TokenType::True),
-            ("var".to_string(), TokenType::Var),
-            ("while".to_string(), TokenType::While),
-        ]);
-
         Self {
             source: source.chars().collect(),
-            keywords,
-            ..Default::default()
+            tokens: vec![],
+            start: 0,
+            current: 0,
+            line: 1,
+            errors: vec![],
+            interpolations: vec![],
         }
     }
 
-    pub fn scan_tokens(&mut self) -> &Vec<Token> {
+    // Starts over on new source, keeping the buffers' allocations.
+    pub fn reset(&mut self, source: String) {
+        self.source.clear();
+        self.source.extend(source.chars());
+        self.tokens.clear();
+        self.start = 0;
+        self.current = 0;
+        self.line = 1;
+        self.errors.clear();
+        self.interpolations.clear();
+    }
+
+    pub fn scan_tokens(&mut self) -> Result<&Vec<Token>> {
         while !self.at_end() {
             self.start = self.current;
             self.scan_token()
         }
-        self.tokens
-            .push(Token::new(TokenType::Eof, "".to_string(), self.line));
-        &self.tokens
+        if !self.interpolations.is_empty() {
+            self.error("Unterminated string interpolation");
+        }
+        match self.errors.len() {
+            0 => {}
+            1 => return Err(self.errors.remove(0)),
+            _ => return Err(Error::Multiple(std::mem::take(&mut self.errors))),
+        }
+        self.tokens.push(Token::new(
+            TokenType::Eof,
+            "".to_string(),
+            self.line,
+            self.current,
+            self.current,
+        ));
+        Ok(&self.tokens)
     }
 
     fn scan_token(&mut self) {
@@ -56,8 +94,24 @@ impl Scanner {
         match c {
             '(' => self.add_token(TokenType::LeftParen),
             ')' => self.add_token(TokenType::RightParen),
-            '{' => self.add_token(TokenType::LeftBrace),
-            '}' => self.add_token(TokenType::RightBrace),
+            '{' => {
+                if let Some(depth) = self.interpolations.last_mut() {
+                    *depth += 1;
+                }
+                self.add_token(TokenType::LeftBrace)
+            }
+            '}' => match self.interpolations.last_mut() {
+                Some(0) => {
+                    self.interpolations.pop();
+                    self.string();
+                }
+                Some(depth) => {
+                    *depth -= 1;
+                    self.add_token(TokenType::RightBrace)
+                }
+                None => self.add_token(TokenType::RightBrace),
+            },
+            ':' => self.add_token(TokenType::Colon),
             ',' => self.add_token(TokenType::Comma),
             '.' => self.add_token(TokenType::Dot),
             '-' => self.add_token(TokenType::Minus),
@@ -96,9 +150,10 @@ impl Scanner {
                     self.add_token(TokenType::Less);
                 }
             }
+            '?' if self.match_char('?') => self.add_token(TokenType::QuestionQuestion),
             '/' => {
                 if self.match_char('/') {
-                    while self.peek() != '\n' && !self.at_end() {
+                    while self.peek().is_some_and(|c| c != '\n') {
                         self.advance();
                     }
                 } else {
@@ -107,13 +162,10 @@ impl Scanner {
             }
             '"' => self.string(),
             c if c.is_ascii_digit() => self.number(),
-            c if c.is_alphabetic() => self.identifier(),
+            c if c.is_alphabetic() || c == '_' => self.identifier(),
             ' ' | '\r' | '\t' => (),
             '\n' => self.line += 1,
-            _ => {
-                // TODO: add error logging
-                unimplemented!()
-            }
+            c => self.error(&format!("Unexpected character '{}'", c)),
         };
     }
 
@@ -134,47 +186,75 @@ impl Scanner {
         true
     }
 
-    fn peek(&self) -> char {
-        if self.at_end() {
-            return '\0';
-        }
-        self.source[self.current]
+    fn peek(&self) -> Option<char> {
+        self.source.get(self.current).copied()
     }
 
-    fn peek_next(&self) -> char {
-        if self.current + 1 >= self.source.len() {
-            return '\0';
-        };
-        self.source[self.current + 1]
+    fn peek_next(&self) -> Option<char> {
+        self.source.get(self.current + 1).copied()
     }
 
     fn string(&mut self) {
-        while self.peek() != '"' && !self.at_end() {
-            if self.peek() == '\n' {
-                self.line += 1
+        let mut literal = String::new();
+        while self.peek().is_some_and(|c| c != '"') {
+            match self.advance() {
+                '\\' if self.peek().is_some_and(|c| ESCAPES.contains(&c)) => {
+                    literal.push(match self.advance() {
+                        'n' => '\n',
+                        'r' => '\r',
+                        't' => '\t',
+                        '0' => '\0',
+                        c => c,
+                    })
+                }
+                // A backslash ending the line continues the string without the newline.
+                '\\' if self.peek() == Some('\n') => {
+                    self.advance();
+                    self.line += 1;
+                }
+                '\\' if self.peek() == Some('\r') && self.peek_next() == Some('\n') => {
+                    self.advance();
+                    self.advance();
+                    self.line += 1;
+                }
+                '$' if self.peek() == Some('{') => {
+                    self.advance();
+                    self.interpolations.push(0);
+                    self.add_token(TokenType::Interpolation(literal));
+                    return;
+                }
+                c => {
+                    if c == '\n' {
+                        self.line += 1
+                    }
+                    literal.push(c);
+                }
             }
-            self.advance();
         }
         if self.at_end() {
-            // TODO: Better error handling
-            eprintln!("Unterminated string");
+            self.error("Unterminated string");
             return;
         }
 
         // The closing "
         self.advance();
 
-        let literal = &self.source[self.start + 1..self.current - 1];
-        self.add_token(TokenType::String(literal.iter().collect()));
+        self.add_token(TokenType::String(literal));
     }
 
     fn number(&mut self) {
-        while self.peek().is_ascii_digit() {
+        if self.source[self.start] == '0' {
+            if let Some((radix, name)) = self.peek().and_then(radix) {
+                self.advance();
+                return self.radix_number(radix, name);
+            }
+        }
+        while self.peek().is_some_and(|c| c.is_ascii_digit()) {
             self.advance();
         }
-        if self.peek() == '.' && self.peek_next().is_ascii_digit() {
+        if self.peek() == Some('.') && self.peek_next().is_some_and(|c| c.is_ascii_digit()) {
             self.advance();
-            while self.peek().is_ascii_digit() {
+            while self.peek().is_some_and(|c| c.is_ascii_digit()) {
                 self.advance();
             }
         }
@@ -188,26 +268,343 @@ impl Scanner {
         ));
     }
 
+    // `0x`, `0o` and `0b` integers. `0b102` is a bad digit, not `0b10` then `2`.
+    fn radix_number(&mut self, radix: u32, name: &str) {
+        while self.peek().is_some_and(|c| c.is_ascii_alphanumeric()) {
+            self.advance();
+        }
+        let digits: String = self.source[self.start + 2..self.current].iter().collect();
+        if digits.is_empty() {
+            return self.error(&format!("Expect digits in {} literal", name));
+        }
+        if let Some(c) = digits.chars().find(|c| !c.is_digit(radix)) {
+            return self.error(&format!("Invalid digit '{}' in {} literal", c, name));
+        }
+        match u64::from_str_radix(&digits, radix) {
+            Ok(num) => self.add_token(TokenType::Number(num as f64)),
+            Err(_) => self.error(&format!("Too many digits in {} literal", name)),
+        }
+    }
+
     fn identifier(&mut self) {
-        while self.peek().is_alphanumeric() {
+        while self.peek().is_some_and(|c| c.is_alphanumeric() || c == '_') {
             self.advance();
         }
-        let text = &self.source[self.start..self.current];
-        let token_type = self
-            .keywords
-            .get(&text.iter().collect::<String>())
-            .unwrap_or(&TokenType::Identifier)
-            .to_owned();
-        self.add_token(token_type);
+        let text: String = self.source[self.start..self.current].iter().collect();
+        let token_type = keyword(&text).unwrap_or(TokenType::Identifier);
+        self.add_token_with_text(token_type, text);
     }
 
     fn add_token(&mut self, token_type: TokenType) {
-        let text = &self.source[self.start..self.current];
-        let token = Token::new(token_type, text.iter().collect(), self.line);
+        let text = self.source[self.start..self.current].iter().collect();
+        self.add_token_with_text(token_type, text);
+    }
+
+    fn add_token_with_text(&mut self, token_type: TokenType, text: String) {
+        let token = Token::new(token_type, text, self.line, self.start, self.current);
         self.tokens.push(token);
     }
 
+    fn error(&mut self, msg: &str) {
+        self.errors.push(Error::ScanError {
+            file: None,
+            line: self.line,
+            msg: msg.to_string(),
+        });
+    }
+
     fn at_end(&self) -> bool {
         self.current >= self.source.len()
     }
 }
+
+fn radix(prefix: char) -> Option<(u32, &'static str)> {
+    match prefix {
+        'x' | 'X' => Some((16, "hexadecimal")),
+        'o' | 'O' => Some((8, "octal")),
+        'b' | 'B' => Some((2, "binary")),
+        _ => None,
+    }
+}
+
+fn keyword(text: &str) -> Option<TokenType> {
+    let token_type = match text {
+        "and" => TokenType::And,
+        "break" => TokenType::Break,
+        "case" => TokenType::Case,
+        "catch" => TokenType::Catch,
+        "class" => TokenType::Class,
+        "const" => TokenType::Const,
+        "continue" => TokenType::Continue,
+        "default" => TokenType::Default,
+        "do" => TokenType::Do,
+        "else" => TokenType::Else,
+        "false" => TokenType::False,
+        "for" => TokenType::For,
+        "fun" => TokenType::Fun,
+        "if" => TokenType::If,
+        "is" => TokenType::Is,
+        "nil" => TokenType::Nil,
+        "or" => TokenType::Or,
+        "print" => TokenType::Print,
+        "repeat" => TokenType::Repeat,
+        "return" => TokenType::Return,
+        "super" => TokenType::Super,
+        "switch" => TokenType::Switch,
+        "this" => TokenType::This,
+        "throw" => TokenType::Throw,
+        "true" => TokenType::True,
+        "try" => TokenType::Try,
+        "var" => TokenType::Var,
+        "while" => TokenType::While,
+        _ => return None,
+    };
+    Some(token_type)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn string_literal(source: &str) -> String {
+        let mut scanner = Scanner::new(source.to_string());
+        let tokens = scanner.scan_tokens().unwrap();
+        match &tokens[0].token_type {
+            TokenType::String(literal) => literal.clone(),
+            other => panic!("Expected a string, got {other:?}"),
+        }
+    }
+
+    #[test]
+    fn line_numbers_stay_exact_across_a_large_file() {
+        // Every 100th entry is a blank line, a comment or a two-line string.
+        let mut source = String::new();
+        for n in 1..=1_000 {
+            match n % 300 {
+                _ if n % 100 != 0 => source += &format!("var v{n} = {n};\n"),
+                0 => source += "\n",
+                100 => source += "// comment\n",
+                _ => source += "var s = \"two\nlines\";\n",
+            }
+        }
+        let mut scanner = Scanner::new(source.clone());
+        let tokens = scanner.scan_tokens().unwrap();
+        for token in tokens {
+            if let TokenType::Number(n) = token.token_type {
+                let n = n as usize;
+                // Each string before this point added a line.
+                let strings = (n + 100) / 300;
+                assert_eq!(token.line, n + strings, "{n}");
+            }
+        }
+        assert_eq!(tokens.last().unwrap().line, source.lines().count() + 1);
+    }
+
+    #[test]
+    fn escapes() {
+        assert_eq!(string_literal(r#""a\nb\r\t\0\"\\\$""#), "a\nb\r\t\0\"\\$");
+        assert_eq!(string_literal(r#""\q""#), "\\q");
+    }
+
+    #[test]
+    fn backslash_newline_continues_a_string() {
+        assert_eq!(string_literal("\"one \\\n two\""), "one  two");
+        assert_eq!(string_literal("\"one \\\r\n two\""), "one  two");
+        assert_eq!(string_literal("\"one\ntwo\""), "one\ntwo");
+
+        let mut scanner = Scanner::new("\"a \\\r\n b\" x".to_string());
+        let tokens = scanner.scan_tokens().unwrap();
+        assert_eq!(tokens[1].line, 2);
+    }
+
+    #[test]
+    fn nul_is_not_the_end_of_input() {
+        assert_eq!(string_literal("\"a\0b\""), "a\0b");
+        let mut scanner = Scanner::new("1\0".to_string());
+        assert!(scanner.scan_tokens().is_err());
+    }
+
+    #[test]
+    fn a_trailing_comment_needs_no_final_newline() {
+        for source in ["var x = 1; // trailing", "print 2; //", "//"] {
+            let mut scanner = Scanner::new(source.to_string());
+            let tokens = scanner.scan_tokens().unwrap();
+            assert_eq!(
+                tokens.last().unwrap().token_type,
+                TokenType::Eof,
+                "{source}"
+            );
+            assert!(
+                tokens.iter().all(|token| !token.text.contains("//")),
+                "{source}"
+            );
+        }
+        let mut scanner = Scanner::new("var x = 1; // trailing".to_string());
+        let types: Vec<TokenType> = scanner
+            .scan_tokens()
+            .unwrap()
+            .iter()
+            .map(|token| token.token_type.clone())
+            .collect();
+        assert_eq!(
+            types,
+            [
+                TokenType::Var,
+                TokenType::Identifier,
+                TokenType::Equal,
+                TokenType::Number(1.0),
+                TokenType::Semicolon,
+                TokenType::Eof
+            ]
+        );
+    }
+
+    fn number(source: &str) -> Result<f64> {
+        let mut scanner = Scanner::new(source.to_string());
+        match scanner.scan_tokens()?[0].token_type {
+            TokenType::Number(num) => Ok(num),
+            ref other => panic!("Expected a number, got {other:?}"),
+        }
+    }
+
+    #[test]
+    fn radix_literals() {
+        let cases = [
+            ("0xff", 255.0),
+            ("0XFF", 255.0),
+            ("0b1010", 10.0),
+            ("0o17", 15.0),
+            ("0x0", 0.0),
+        ];
+        for (source, expected) in cases {
+            assert_eq!(number(source).unwrap(), expected, "{source}");
+        }
+        for source in [
+            "0x",
+            "0b",
+            "0o",
+            "0b102",
+            "0o8",
+            "0xfg",
+            "0x1ffffffffffffffff",
+        ] {
+            let err = number(source).unwrap_err();
+            assert!(matches!(err, Error::ScanError { .. }), "{source}: {err}");
+        }
+    }
+
+    #[test]
+    fn lexical_errors_are_scan_errors() {
+        let mut scanner = Scanner::new("var a = 1;\n@ \"open".to_string());
+        let Err(Error::Multiple(errors)) = scanner.scan_tokens() else {
+            panic!("Expected both errors");
+        };
+        assert!(matches!(
+            &errors[..],
+            [
+                Error::ScanError { line: 2, .. },
+                Error::ScanError { line: 2, .. },
+            ]
+        ));
+    }
+
+    #[test]
+    fn tokens_record_char_offsets() {
+        let source = "var é = \"ü\";\nprint é;";
+        let mut scanner = Scanner::new(source.to_string());
+        let chars: Vec<char> = source.chars().collect();
+        let tokens = scanner.scan_tokens().unwrap();
+        let spans: Vec<(usize, usize)> = tokens.iter().map(|t| (t.start, t.end)).collect();
+        assert_eq!(
+            spans,
+            [
+                (0, 3),
+                (4, 5),
+                (6, 7),
+                (8, 11),
+                (11, 12),
+                (13, 18),
+                (19, 20),
+                (20, 21),
+                (21, 21)
+            ]
+        );
+        for token in &tokens[..tokens.len() - 1] {
+            let text: String = chars[token.start..token.end].iter().collect();
+            assert_eq!(text, token.text);
+        }
+    }
+
+    #[test]
+    fn keywords_resolve_and_other_words_are_identifiers() {
+        let source = "and break case catch class const continue default do else false for fun \
+                      if is nil or print repeat return super switch this throw true try var while";
+        let mut scanner = Scanner::new(source.to_string());
+        let tokens = scanner.scan_tokens().unwrap();
+        for token in &tokens[..tokens.len() - 1] {
+            assert_ne!(token.token_type, TokenType::Identifier);
+            assert_eq!(keyword(&token.text), Some(token.token_type.clone()));
+        }
+        assert_eq!(tokens.len(), 29);
+        assert_eq!(tokens[0].token_type, TokenType::And);
+        assert_eq!(tokens[27].token_type, TokenType::While);
+
+        let mut scanner = Scanner::new("android classy _if While printer".to_string());
+        let tokens = scanner.scan_tokens().unwrap();
+        assert!(tokens[..tokens.len() - 1]
+            .iter()
+            .all(|token| token.token_type == TokenType::Identifier));
+    }
+
+    #[test]
+    fn identifier_heavy_source_keeps_every_name() {
+        let names: Vec<String> = (0..1000).map(|i| format!("name_{i}")).collect();
+        let source = names
+            .iter()
+            .map(|name| format!("var {name} = {name};"))
+            .collect::<Vec<_>>()
+            .join("\n");
+        let mut scanner = Scanner::new(source);
+        let tokens = scanner.scan_tokens().unwrap();
+        let identifiers: Vec<&str> = tokens
+            .iter()
+            .filter(|token| token.token_type == TokenType::Identifier)
+            .map(|token| token.text.as_str())
+            .collect();
+        assert_eq!(identifiers.len(), 2000);
+        for (pair, name) in identifiers.chunks(2).zip(&names) {
+            assert_eq!(pair, [name.as_str(), name.as_str()]);
+        }
+    }
+
+    #[test]
+    fn a_reset_scanner_scans_each_source_independently() {
+        let mut scanner = Scanner::default();
+        scanner.reset("var a = 1;\nprint a;".to_string());
+        let first: Vec<(TokenType, usize)> = scanner
+            .scan_tokens()
+            .unwrap()
+            .iter()
+            .map(|token| (token.token_type.clone(), token.line))
+            .collect();
+        assert_eq!(first.len(), 9);
+        assert_eq!(first[5], (TokenType::Print, 2));
+
+        scanner.reset("\"${x}\" @".to_string());
+        assert!(scanner.scan_tokens().is_err());
+
+        scanner.reset("x".to_string());
+        let tokens = scanner.scan_tokens().unwrap();
+        assert_eq!(tokens.len(), 2);
+        assert_eq!(tokens[0].token_type, TokenType::Identifier);
+        assert_eq!((tokens[0].line, tokens[0].start, tokens[0].end), (1, 0, 1));
+    }
+
+    #[test]
+    fn default_scanner_starts_on_line_one() {
+        let mut scanner = Scanner::default();
+        scanner.reset("x".to_string());
+        assert_eq!(scanner.scan_tokens().unwrap()[0].line, 1);
+        assert_eq!(Scanner::default().scan_tokens().unwrap()[0].line, 1);
+    }
+}
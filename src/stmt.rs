@@ -7,16 +7,32 @@ pub enum Stmt {
     },
     FunctionDeclaration(FunctionDeclaration),
     Print {
-        expr: Box<Expr>,
+        exprs: Vec<Expr>,
     },
     Return {
         keyword: Token,
         value: Option<Box<Expr>>,
     },
+    Throw {
+        keyword: Token,
+        value: Box<Expr>,
+    },
+    Break {
+        keyword: Token,
+        label: Option<Token>,
+    },
+    Continue {
+        keyword: Token,
+        label: Option<Token>,
+    },
     VariableDeclaration {
         name: Token,
         initializer: Option<Box<Expr>>,
     },
+    ConstDeclaration {
+        name: Token,
+        initializer: Box<Expr>,
+    },
     If {
         condition: Box<Expr>,
         then_branch: Box<Stmt>,
@@ -25,38 +41,115 @@ pub enum Stmt {
     While {
         condition: Box<Expr>,
         body: Box<Stmt>,
+        label: Option<Token>,
+    },
+    // The increment still runs after a `continue`.
+    For {
+        initializer: Option<Box<Stmt>>,
+        condition: Option<Box<Expr>>,
+        increment: Option<Box<Expr>>,
+        body: Box<Stmt>,
+        label: Option<Token>,
+    },
+    Switch {
+        discriminant: Box<Expr>,
+        cases: Vec<SwitchCase>,
+        default: Option<Vec<Stmt>>,
+    },
+    DoWhile {
+        body: Box<Stmt>,
+        condition: Box<Expr>,
+        label: Option<Token>,
+    },
+    Repeat {
+        keyword: Token,
+        count: Box<Expr>,
+        body: Box<Stmt>,
+        label: Option<Token>,
+    },
+    // `return` and `break` pass straight through.
+    Try {
+        body: Vec<Stmt>,
+        error_name: Token,
+        handler: Vec<Stmt>,
     },
     Block {
         stmts: Vec<Stmt>,
     },
+    // Runs in the current scope, unlike Block.
+    Sequence {
+        stmts: Vec<Stmt>,
+    },
 }
 
 #[derive(PartialEq, Clone, Debug)]
 pub struct FunctionDeclaration {
     pub name: Token,
-    pub params: Vec<Token>,
+    pub params: Vec<Parameter>,
+    pub body: Vec<Stmt>,
+}
+
+#[derive(PartialEq, Clone, Debug)]
+pub struct Parameter {
+    pub name: Token,
+    pub default: Option<Expr>,
+}
+
+// Cases don't fall through. An unlabeled `break` leaves the switch, as in C.
+#[derive(PartialEq, Clone, Debug)]
+pub struct SwitchCase {
+    pub value: Expr,
     pub body: Vec<Stmt>,
 }
 
 pub trait Visitor {
     type Out;
     fn visit_expression(&mut self, expr: &Expr) -> Self::Out;
-    fn visit_print(&mut self, expr: &Expr) -> Self::Out;
+    fn visit_print(&mut self, exprs: &[Expr]) -> Self::Out;
     fn visit_function_declaration(
         &mut self,
         function_declaration: &FunctionDeclaration,
     ) -> Self::Out;
     fn visit_return(&mut self, keyword: &Token, value: Option<&Expr>) -> Self::Out;
+    fn visit_throw(&mut self, keyword: &Token, value: &Expr) -> Self::Out;
+    fn visit_break(&mut self, keyword: &Token, label: Option<&Token>) -> Self::Out;
+    fn visit_continue(&mut self, keyword: &Token, label: Option<&Token>) -> Self::Out;
     fn visit_variable_declaration(&mut self, name: &Token, initializer: Option<&Expr>)
         -> Self::Out;
+    fn visit_const_declaration(&mut self, name: &Token, initializer: &Expr) -> Self::Out;
+    fn visit_try(&mut self, body: &[Stmt], error_name: &Token, handler: &[Stmt]) -> Self::Out;
     fn visit_block(&mut self, stmts: &[Stmt]) -> Self::Out;
+    fn visit_sequence(&mut self, stmts: &[Stmt]) -> Self::Out;
     fn visit_if(
         &mut self,
         condition: &Expr,
         then_branch: &Stmt,
         else_branch: Option<&Stmt>,
     ) -> Self::Out;
-    fn visit_while(&mut self, condition: &Expr, body: &Stmt) -> Self::Out;
+    fn visit_switch(
+        &mut self,
+        discriminant: &Expr,
+        cases: &[SwitchCase],
+        default: Option<&[Stmt]>,
+    ) -> Self::Out;
+    fn visit_while(&mut self, condition: &Expr, body: &Stmt, label: Option<&Token>) -> Self::Out;
+    fn visit_for(
+        &mut self,
+        initializer: Option<&Stmt>,
+        condition: Option<&Expr>,
+        increment: Option<&Expr>,
+        body: &Stmt,
+        label: Option<&Token>,
+    ) -> Self::Out;
+    fn visit_do_while(&mut self, body: &Stmt, condition: &Expr, label: Option<&Token>)
+        -> Self::Out;
+    fn visit_repeat(
+        &mut self,
+        keyword: &Token,
+        count: &Expr,
+        body: &Stmt,
+        label: Option<&Token>,
+    ) -> Self::Out;
 }
 
 impl Stmt {
@@ -66,14 +159,59 @@ impl Stmt {
     {
         match self {
             Stmt::Expression { expr } => visitor.visit_expression(expr),
-            Stmt::Print { expr } => visitor.visit_print(expr),
+            Stmt::Print { exprs } => visitor.visit_print(exprs),
             Stmt::FunctionDeclaration(function) => visitor.visit_function_declaration(function),
-            Stmt::While { condition, body } => visitor.visit_while(condition, body),
+            Stmt::While {
+                condition,
+                body,
+                label,
+            } => visitor.visit_while(condition, body, label.as_ref()),
+            Stmt::For {
+                initializer,
+                condition,
+                increment,
+                body,
+                label,
+            } => visitor.visit_for(
+                initializer.as_deref(),
+                condition.as_deref(),
+                increment.as_deref(),
+                body,
+                label.as_ref(),
+            ),
+            Stmt::Switch {
+                discriminant,
+                cases,
+                default,
+            } => visitor.visit_switch(discriminant, cases, default.as_deref()),
+            Stmt::DoWhile {
+                body,
+                condition,
+                label,
+            } => visitor.visit_do_while(body, condition, label.as_ref()),
+            Stmt::Repeat {
+                keyword,
+                count,
+                body,
+                label,
+            } => visitor.visit_repeat(keyword, count, body, label.as_ref()),
             Stmt::Return { keyword, value } => visitor.visit_return(keyword, value.as_deref()),
+            Stmt::Throw { keyword, value } => visitor.visit_throw(keyword, value),
+            Stmt::Break { keyword, label } => visitor.visit_break(keyword, label.as_ref()),
+            Stmt::Continue { keyword, label } => visitor.visit_continue(keyword, label.as_ref()),
             Stmt::VariableDeclaration { name, initializer } => {
                 visitor.visit_variable_declaration(name, initializer.as_deref())
             }
+            Stmt::ConstDeclaration { name, initializer } => {
+                visitor.visit_const_declaration(name, initializer)
+            }
+            Stmt::Try {
+                body,
+                error_name,
+                handler,
+            } => visitor.visit_try(body, error_name, handler),
             Stmt::Block { stmts } => visitor.visit_block(stmts.as_slice()),
+            Stmt::Sequence { stmts } => visitor.visit_sequence(stmts.as_slice()),
             Stmt::If {
                 condition,
                 then_branch,
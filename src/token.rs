@@ -7,6 +7,7 @@ pub enum TokenType {
     RightParen,
     LeftBrace,
     RightBrace,
+    Colon,
     Comma,
     Dot,
     Minus,
@@ -24,27 +25,42 @@ pub enum TokenType {
     GreaterEqual,
     Less,
     LessEqual,
+    QuestionQuestion,
 
     // Literals.
     Identifier,
     String(String),
+    // Text up to a `${`, followed by the embedded expression's tokens.
+    Interpolation(String),
     Number(f64),
 
     // Keywords.
     And,
+    Break,
+    Case,
+    Catch,
     Class,
+    Const,
+    Continue,
+    Default,
+    Do,
     Else,
     False,
     Fun,
     For,
     If,
+    Is,
     Nil,
     Or,
     Print,
+    Repeat,
     Return,
     Super,
+    Switch,
     This,
+    Throw,
     True,
+    Try,
     Var,
     While,
 
@@ -56,14 +72,19 @@ pub struct Token {
     pub token_type: TokenType,
     pub text: String,
     pub line: usize,
+    // Char offsets into the source, `end` exclusive.
+    pub start: usize,
+    pub end: usize,
 }
 
 impl Token {
-    pub fn new(token_type: TokenType, text: String, line: usize) -> Self {
+    pub fn new(token_type: TokenType, text: String, line: usize, start: usize, end: usize) -> Self {
         Self {
             token_type,
             text,
             line,
+            start,
+            end,
         }
     }
 }
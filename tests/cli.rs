@@ -0,0 +1,145 @@
+use std::io::Write;
+use std::process::{Command, Output, Stdio};
+use std::{env, fs, path::PathBuf};
+
+fn jlox(args: &[&str], stdin: &str) -> Output {
+    let mut child = Command::new(env!("CARGO_BIN_EXE_jlox"))
+        .args(args)
+        .stdin(Stdio::piped())
+        .stdout(Stdio::piped())
+        .stderr(Stdio::piped())
+        .spawn()
+        .unwrap();
+    child
+        .stdin
+        .take()
+        .unwrap()
+        .write_all(stdin.as_bytes())
+        .unwrap();
+    child.wait_with_output().unwrap()
+}
+
+// Writes `source` to a script in the temp dir, named after the test so parallel tests
+// don't share one.
+fn script(name: &str, source: &str) -> PathBuf {
+    let path = env::temp_dir().join(format!("jlox-{}-{name}.lox", std::process::id()));
+    fs::write(&path, source).unwrap();
+    path
+}
+
+fn run_script(name: &str, source: &str, flags: &[&str]) -> Output {
+    let path = script(name, source);
+    let mut args = flags.to_vec();
+    args.push(path.to_str().unwrap());
+    let output = jlox(&args, "");
+    fs::remove_file(path).unwrap();
+    output
+}
+
+fn stdout(output: &Output) -> String {
+    String::from_utf8(output.stdout.clone()).unwrap()
+}
+
+fn stderr(output: &Output) -> String {
+    String::from_utf8(output.stderr.clone()).unwrap()
+}
+
+#[test]
+fn scan_errors_exit_with_65() {
+    let output = run_script("scan-error", "print 1;\nprint @;\n", &[]);
+    assert_eq!(output.status.code(), Some(65));
+    assert!(stderr(&output).contains(":2: Unexpected character '@'"));
+    assert_eq!(stdout(&output), "");
+}
+
+#[test]
+fn eval_prints_the_value() {
+    let output = jlox(&["--eval", "1 < 2"], "");
+    assert!(output.status.success());
+    assert_eq!(stdout(&output), "true\n");
+
+    let output = jlox(&["--eval", "1 +"], "");
+    assert_eq!(output.status.code(), Some(65));
+    let output = jlox(&["--eval", "1 + nil"], "");
+    assert_eq!(output.status.code(), Some(70));
+}
+
+#[test]
+fn check_reports_errors_without_running() {
+    let output = run_script("check-valid", "print \"ran\";\n", &["--check"]);
+    assert!(output.status.success());
+    assert_eq!(stdout(&output), "");
+
+    let output = run_script("check-invalid", "print \"ran\";\nvar = 1;\n", &["--check"]);
+    assert_eq!(output.status.code(), Some(65));
+    assert_eq!(stdout(&output), "");
+    assert!(stderr(&output).contains(":2: "));
+
+    // Runtime errors are out of reach without running.
+    let output = run_script("check-runtime", "print 1 + nil;\n", &["--check"]);
+    assert!(output.status.success());
+}
+
+#[test]
+fn errors_name_the_script_and_line() {
+    let output = run_script("file-name", "var a = 1;\nprint a + nil;\n", &[]);
+    let path = script("file-name", "");
+    fs::remove_file(&path).unwrap();
+    assert_eq!(output.status.code(), Some(70));
+    assert!(stderr(&output).starts_with(&format!("{}:2: ", path.display())));
+
+    let output = jlox(&["--eval", "1 +"], "");
+    assert!(stderr(&output).starts_with("<eval>:1: "));
+}
+
+#[test]
+fn color_is_only_used_on_a_terminal_unless_forced() {
+    let source = "print nil + 1;\n";
+    // stderr is a pipe here, so auto means no color.
+    for flags in [&[][..], &["--color=auto"], &["--color=never"]] {
+        let output = run_script("no-color", source, flags);
+        assert!(!stderr(&output).contains('\x1b'), "{flags:?}");
+    }
+    let output = run_script("color", source, &["--color=always"]);
+    assert!(stderr(&output).starts_with("\x1b[31m"));
+    assert!(jlox(&["--color=sometimes"], "").status.code() != Some(0));
+}
+
+#[test]
+fn runtime_errors_show_the_failing_line() {
+    let source = "fun add(a, b) {\n  return a + b;\n}\nadd(1, nil);\n";
+    let output = run_script("context", source, &[]);
+    assert!(stderr(&output).ends_with("\n    2 |   return a + b;\n"));
+
+    // REPL lines all count from 1, so there is no line to show.
+    let output = jlox(&[], "fun add(a, b) { return a + b; }\nadd(1, nil);\n");
+    assert!(!stderr(&output).contains(" | "));
+}
+
+#[test]
+fn debug_values_prints_the_debug_form() {
+    let source = "print 3;\nprint \"s\";\nprint nil;\n";
+    let output = run_script("debug-values", source, &["--debug-values"]);
+    assert_eq!(stdout(&output), "Number(3.0)\nString(\"s\")\nNil\n");
+    let output = run_script("plain-values", source, &[]);
+    assert_eq!(stdout(&output), "3\ns\nnull\n");
+}
+
+#[test]
+fn trace_calls_indents_nested_calls() {
+    let source = "fun f(n) { if (n == 0) return 0; return f(n - 1); }\nf(2);\n";
+    let output = run_script("trace-calls", source, &["--trace-calls"]);
+    let expected = "call f(2)\n  call f(1)\n    call f(0)\n    return 0\n  return 0\nreturn 0\n";
+    assert_eq!(stderr(&output), expected);
+
+    let source = "fun f() { return nil + 1; }\nfun g() { f(); }\ng();\n";
+    let output = run_script("trace-unwind", source, &["--trace-calls"]);
+    assert!(stderr(&output).starts_with("call g()\n  call f()\n  unwind f\nunwind g\n"));
+}
+
+#[test]
+fn print_joins_its_values_with_spaces() {
+    let source = "print 1, \"a\", nil;\nprint 2;\nprint 1 + 2, \"x\" + \"y\";\n";
+    let output = run_script("print-list", source, &[]);
+    assert_eq!(stdout(&output), "1 a null\n2\n3 xy\n");
+}